@@ -0,0 +1,214 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use libc;
+
+/// How a file is placed at its new name.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Rename the file in place, which fails if the target is on another file system.
+    Rename,
+    /// Move the file, copying, verifying and deleting it if the target is on another file system.
+    Move,
+    /// Copy the file, verifying the copy, and leave the original untouched.
+    Copy,
+    /// Create a hard link to the original.
+    Hardlink,
+    /// Create a symbolic link to the original.
+    Symlink,
+    /// Create a copy-on-write clone of the original, on file systems which support it.
+    Reflink,
+}
+
+impl Default for Action {
+    fn default() -> Action { Action::Rename }
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Action::Rename   => "rename",
+            Action::Move     => "move",
+            Action::Copy     => "copy",
+            Action::Hardlink => "hardlink",
+            Action::Symlink  => "symlink",
+            Action::Reflink  => "reflink",
+        }
+    }
+
+    /// Whether the source no longer exists once the action has been applied.
+    pub fn vacates_source(&self) -> bool {
+        match *self {
+            Action::Rename | Action::Move => true,
+            _ => false
+        }
+    }
+
+    /// Places the source at the target, creating the target's directory if it does not exist.
+    pub fn apply(&self, source: &Path, target: &Path) -> io::Result<()> {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        match *self {
+            Action::Rename   => fs::rename(source, target),
+            Action::Move     => move_file(source, target),
+            Action::Copy     => copy_file(source, target),
+            Action::Hardlink => fs::hard_link(source, target),
+            Action::Symlink  => symlink(&source.canonicalize()?, target),
+            Action::Reflink  => reflink(source, target),
+        }
+    }
+
+    /// Reverses a previously applied action, by moving the target back to the source, or by removing the target if
+    /// the source was left in place.
+    pub fn revert(&self, source: &Path, target: &Path) -> io::Result<()> {
+        match *self {
+            Action::Rename => fs::rename(target, source),
+            Action::Move   => move_file(target, source),
+            _              => fs::remove_file(target)
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = ();
+    fn from_str(action: &str) -> Result<Action, ()> {
+        match action {
+            "rename"   => Ok(Action::Rename),
+            "move"     => Ok(Action::Move),
+            "copy"     => Ok(Action::Copy),
+            "hardlink" => Ok(Action::Hardlink),
+            "symlink"  => Ok(Action::Symlink),
+            "reflink"  => Ok(Action::Reflink),
+            _          => Err(())
+        }
+    }
+}
+
+/// Renames a file, falling back to copying, verifying and deleting it if the target is on another file system.
+fn move_file(source: &Path, target: &Path) -> io::Result<()> {
+    match fs::rename(source, target) {
+        Err(ref why) if is_cross_device(why) => {
+            copy_file(source, target)?;
+            fs::remove_file(source)
+        },
+        result => result
+    }
+}
+
+/// Copies a file, and then compares the copy against the original, removing the copy if they differ.
+fn copy_file(source: &Path, target: &Path) -> io::Result<()> {
+    if target.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, "the target of the copy already exists"));
+    }
+
+    fs::copy(source, target)?;
+    match identical(source, target) {
+        Ok(true) => Ok(()),
+        Ok(false) => {
+            let _ = fs::remove_file(target);
+            Err(io::Error::new(io::ErrorKind::InvalidData, "the copy does not match the original"))
+        },
+        Err(why) => {
+            let _ = fs::remove_file(target);
+            Err(why)
+        }
+    }
+}
+
+/// Compares the contents of two files.
+fn identical(first: &Path, second: &Path) -> io::Result<bool> {
+    let (mut first, mut second) = (File::open(first)?, File::open(second)?);
+    if first.metadata()?.len() != second.metadata()?.len() { return Ok(false) }
+
+    let mut first_buffer = vec![0u8; 64 * 1024];
+    let mut second_buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = first.read(&mut first_buffer)?;
+        if read == 0 { return Ok(true) }
+        second.read_exact(&mut second_buffer[..read])?;
+        if first_buffer[..read] != second_buffer[..read] { return Ok(false) }
+    }
+}
+
+/// Whether a rename failed because its target is on another file system.
+#[cfg(unix)]
+pub fn is_cross_device(why: &io::Error) -> bool { why.raw_os_error() == Some(libc::EXDEV) }
+
+#[cfg(windows)]
+pub fn is_cross_device(why: &io::Error) -> bool {
+    // ERROR_NOT_SAME_DEVICE
+    why.raw_os_error() == Some(17)
+}
+
+#[cfg(unix)]
+fn symlink(source: &Path, target: &Path) -> io::Result<()> { ::std::os::unix::fs::symlink(source, target) }
+
+#[cfg(windows)]
+fn symlink(source: &Path, target: &Path) -> io::Result<()> { ::std::os::windows::fs::symlink_file(source, target) }
+
+/// Clones the source with the `FICLONE` ioctl, which is supported by Btrfs, XFS and other copy-on-write file systems.
+#[cfg(target_os = "linux")]
+fn reflink(source: &Path, target: &Path) -> io::Result<()> {
+    use std::fs::OpenOptions;
+    use std::os::unix::io::AsRawFd;
+    const FICLONE: libc::c_ulong = 0x40049409;
+
+    let input = File::open(source)?;
+    let output = OpenOptions::new().write(true).create_new(true).open(target)?;
+    if unsafe { libc::ioctl(output.as_raw_fd(), FICLONE as _, input.as_raw_fd()) } != 0 {
+        let why = io::Error::last_os_error();
+        drop(output);
+        let _ = fs::remove_file(target);
+        return Err(why);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_source: &Path, _target: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "reflinks are only supported on Linux"))
+}
+
+/// The identifier of the file system containing the given path, or its nearest existing ancestor.
+#[cfg(unix)]
+pub fn device(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    path.ancestors().filter_map(|ancestor| fs::metadata(ancestor).ok()).next().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+pub fn device(_path: &Path) -> Option<u64> { None }
+
+/// The number of bytes available to unprivileged users on the file system containing the given path, or its
+/// nearest existing ancestor.
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::mem;
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = path.ancestors().find(|ancestor| ancestor.exists()).unwrap_or(path);
+    let existing = CString::new(existing.as_os_str().as_bytes())
+        .map_err(|why| io::Error::new(io::ErrorKind::InvalidInput, why))?;
+    let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statvfs(existing.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> io::Result<u64> { Ok(u64::max_value()) }
+
+#[test]
+fn test_action() {
+    for &action in &[Action::Rename, Action::Move, Action::Copy, Action::Hardlink, Action::Symlink, Action::Reflink] {
+        assert_eq!(action.as_str().parse::<Action>(), Ok(action));
+    }
+    assert_eq!("link".parse::<Action>(), Err(()));
+}
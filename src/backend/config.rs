@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use toml;
+
+use super::{season_pattern, ScanOptions};
+use super::junk::JunkPolicy;
+use super::sort::SortOrder;
+use super::action::Action;
+use super::conflict::ConflictPolicy;
+use super::provider::{tvdb, Ordering};
+
+/// The template used when neither the configuration nor the command line gives one.
+pub const DEFAULT_TEMPLATE: &'static str = "${Series} - ${Season}x${Episode} - ${TVDB_Title}";
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum ConfigError {
+        Io(path: PathBuf, err: io::Error) {
+            display("unable to read {:?}: {}", path, err)
+        }
+        Parse(path: PathBuf, err: toml::de::Error) {
+            display("{:?} is not a valid configuration file: {}", path, err)
+        }
+        ProfileNotFound(name: String) {
+            display("no profile named `{}` is defined in the configuration file", name)
+        }
+        InvalidValue(key: &'static str, value: String) {
+            display("`{}` is not a valid value of `{}` in the configuration file", value, key)
+        }
+    }
+}
+
+/// The contents of `config.toml`. Settings in the `[default]` table apply to every run, and those of a profile, such
+/// as `[profiles.anime]`, apply on top of them when the profile is selected with `--profile`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default)]
+    default:  Profile,
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// A table of settings, whose keys are named after the command line options which override them.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct Profile {
+    template:           Option<String>,
+    pad_length:         Option<u8>,
+    episode_start:      Option<u32>,
+    on_conflict:        Option<String>,
+    action:             Option<String>,
+    target_directory:   Option<String>,
+    max_depth:          Option<usize>,
+    season_patterns:    Option<Vec<String>>,
+    sort:               Option<String>,
+    include_extensions: Option<Vec<String>>,
+    exclude_extensions: Option<Vec<String>>,
+    junk:               Option<String>,
+    junk_durations:     Option<bool>,
+    match_runtime:      Option<bool>,
+    order:              Option<String>,
+    language:           Option<String>,
+    api_key:            Option<String>,
+    pin:                Option<String>,
+}
+
+/// Settings shared by both frontends, which are overridden by command line options.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub template:           String,
+    pub pad_length:         u8,
+    pub episode_start:      u32,
+    /// Each frontend has its own policy when none is configured: the CLI asks, and the GTK interface aborts.
+    pub on_conflict:        Option<ConflictPolicy>,
+    pub action:             Action,
+    pub target_directory:   Option<String>,
+    /// How many levels of directories below a season are searched for episodes.
+    pub max_depth:          usize,
+    /// Patterns which recognize season directories, which are tried before the built-in patterns.
+    pub season_patterns:    Vec<String>,
+    /// The order in which the episodes of a season are numbered.
+    pub sort:               SortOrder,
+    /// Extensions of video files which are not otherwise recognized.
+    pub include_extensions: Vec<String>,
+    /// Extensions which are not to be taken as video files.
+    pub exclude_extensions: Vec<String>,
+    /// What becomes of videos which do not appear to be episodes, such as samples and trailers.
+    pub junk:               JunkPolicy,
+    pub junk_durations:     bool,
+    /// Whether episodes without episode numbers in their names are numbered by their runtimes.
+    pub match_runtime:      bool,
+    pub ordering:           Ordering,
+    /// The language of episode titles, as an ISO 639-2 code such as `eng` or `jpn`.
+    pub language:           String,
+    /// The TVDB API key, which has no default, as each user must register their own.
+    pub api_key:            Option<String>,
+    /// The PIN of a TVDB subscriber, which is required by keys that are licensed to individual users.
+    pub pin:                Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            template:           DEFAULT_TEMPLATE.to_owned(),
+            pad_length:         2,
+            episode_start:      1,
+            on_conflict:        None,
+            action:             Action::Rename,
+            target_directory:   None,
+            max_depth:          ScanOptions::default().max_depth,
+            season_patterns:    Vec::new(),
+            sort:               SortOrder::Natural,
+            include_extensions: Vec::new(),
+            exclude_extensions: Vec::new(),
+            junk:               JunkPolicy::Exclude,
+            junk_durations:     false,
+            match_runtime:      false,
+            ordering:           Ordering::Aired,
+            language:           tvdb::LANGUAGE.to_owned(),
+            api_key:            None,
+            pin:                None,
+        }
+    }
+}
+
+impl Settings {
+    /// Overrides the settings with those given by a profile.
+    fn apply(&mut self, profile: Profile) -> Result<(), ConfigError> {
+        if let Some(template) = profile.template { self.template = template; }
+        if let Some(length) = profile.pad_length { self.pad_length = length; }
+        if let Some(start) = profile.episode_start { self.episode_start = start; }
+        if let Some(policy) = profile.on_conflict { self.on_conflict = Some(parse("on-conflict", policy)?); }
+        if let Some(action) = profile.action { self.action = parse("action", action)?; }
+        if let Some(directory) = profile.target_directory { self.target_directory = Some(directory); }
+        if let Some(depth) = profile.max_depth { self.max_depth = depth; }
+        if let Some(patterns) = profile.season_patterns {
+            if let Some(pattern) = patterns.iter().find(|pattern| season_pattern(pattern).is_err()) {
+                return Err(ConfigError::InvalidValue("season-patterns", pattern.clone()));
+            }
+            self.season_patterns = patterns;
+        }
+        if let Some(order) = profile.sort { self.sort = parse("sort", order)?; }
+        if let Some(extensions) = profile.include_extensions { self.include_extensions = extensions; }
+        if let Some(extensions) = profile.exclude_extensions { self.exclude_extensions = extensions; }
+        if let Some(policy) = profile.junk { self.junk = parse("junk", policy)?; }
+        if let Some(enabled) = profile.junk_durations { self.junk_durations = enabled; }
+        if let Some(enabled) = profile.match_runtime { self.match_runtime = enabled; }
+        if let Some(ordering) = profile.order { self.ordering = parse("order", ordering)?; }
+        if let Some(language) = profile.language { self.language = language; }
+        if let Some(key) = profile.api_key { self.api_key = Some(key); }
+        if let Some(pin) = profile.pin { self.pin = Some(pin); }
+        Ok(())
+    }
+
+    /// The options of a scan, before they are overridden by command line options.
+    pub fn scan_options(&self) -> ScanOptions {
+        let mut options = ScanOptions::default();
+        options.max_depth = self.max_depth;
+        options.sort = self.sort;
+        options.include_extensions = self.include_extensions.clone();
+        options.exclude_extensions = self.exclude_extensions.clone();
+        options.junk = self.junk;
+        options.junk_durations = self.junk_durations;
+        let patterns = self.season_patterns.iter().filter_map(|pattern| season_pattern(pattern).ok());
+        options.season_patterns = patterns.chain(options.season_patterns).collect();
+        options
+    }
+}
+
+/// Reads the settings of the given profile from `$XDG_CONFIG_HOME/tv-renamer/config.toml`. The built-in settings are
+/// used when there is no configuration file, but a profile cannot be selected without one. TVDB credentials may
+/// instead be given by `$TVDB_API_KEY` and `$TVDB_PIN`, which take precedence over the file.
+pub fn load(profile: Option<&str>) -> Result<Settings, ConfigError> {
+    let mut settings = match config_path() {
+        Some(ref path) if path.exists() => {
+            let contents = fs::read_to_string(path).map_err(|why| ConfigError::Io(path.clone(), why))?;
+            let config = toml::from_str::<Config>(&contents).map_err(|why| ConfigError::Parse(path.clone(), why))?;
+            settings(config, profile)?
+        },
+        _ => match profile {
+            Some(name) => return Err(ConfigError::ProfileNotFound(name.to_owned())),
+            None => Settings::default()
+        }
+    };
+
+    if let Some(key) = env::var("TVDB_API_KEY").ok().filter(|key| !key.is_empty()) {
+        settings.api_key = Some(key);
+    }
+    if let Some(pin) = env::var("TVDB_PIN").ok().filter(|pin| !pin.is_empty()) {
+        settings.pin = Some(pin);
+    }
+    Ok(settings)
+}
+
+fn settings(mut config: Config, profile: Option<&str>) -> Result<Settings, ConfigError> {
+    let mut settings = Settings::default();
+    settings.apply(config.default)?;
+    if let Some(name) = profile {
+        let profile = config.profiles.remove(name).ok_or_else(|| ConfigError::ProfileNotFound(name.to_owned()))?;
+        settings.apply(profile)?;
+    }
+    Ok(settings)
+}
+
+/// The configuration file: `$XDG_CONFIG_HOME/tv-renamer/config.toml`, which defaults to
+/// `~/.config/tv-renamer/config.toml`.
+fn config_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::home_dir().map(|home| home.join(".config")))
+        .map(|config| config.join("tv-renamer/config.toml"))
+}
+
+fn parse<T: FromStr>(key: &'static str, value: String) -> Result<T, ConfigError> {
+    value.parse::<T>().map_err(|_| ConfigError::InvalidValue(key, value))
+}
+
+#[test]
+fn test_profiles() {
+    let config = toml::from_str::<Config>(r#"
+        [default]
+        pad-length = 3
+        on-conflict = "skip"
+
+        [profiles.anime]
+        template = "${Series} - ${Episode} - ${TVDB_Title}"
+        order = "absolute"
+        language = "jpn"
+    "#).unwrap();
+
+    let anime = settings(config, Some("anime")).unwrap();
+    assert_eq!(anime.template, "${Series} - ${Episode} - ${TVDB_Title}");
+    assert_eq!((anime.pad_length, anime.episode_start), (3, 1));
+    assert_eq!(anime.on_conflict, Some(ConflictPolicy::Skip));
+    assert_eq!((anime.ordering, anime.language.as_str()), (Ordering::Absolute, "jpn"));
+
+    let config = toml::from_str::<Config>("[default]\nseason-patterns = ['^Book ([0-9]+)$']").unwrap();
+    let patterns = settings(config, None).unwrap().scan_options().season_patterns;
+    assert_eq!(patterns.len(), ScanOptions::default().season_patterns.len() + 1);
+    assert!(patterns[0].is_match("book 2"));
+
+    let config = toml::from_str::<Config>("[default]\nseason-patterns = ['^Book [0-9]+$']").unwrap();
+    assert!(settings(config, None).is_err());
+
+    let config = toml::from_str::<Config>("[profiles.broken]\naction = \"teleport\"").unwrap();
+    assert!(settings(config, Some("broken")).is_err());
+    assert!(settings(Config::default(), Some("plex")).is_err());
+    assert!(toml::from_str::<Config>("[default]\npad_length = 3").is_err());
+}
@@ -0,0 +1,56 @@
+use std::str::FromStr;
+
+/// Determines what happens when the target of a rename already exists.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConflictPolicy {
+    /// Leave the episode, and its companion files, with their current names.
+    Skip,
+    /// Replace the existing file.
+    Overwrite,
+    /// Keep both files by adding a numbered suffix, such as ` (2)`, to the new name.
+    Suffix,
+    /// Move the existing file into the Trash before renaming.
+    Trash,
+    /// Ask the user which of the above to do for each conflict.
+    Ask,
+    /// Stop without renaming anything.
+    Abort,
+}
+
+impl ConflictPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ConflictPolicy::Skip      => "skip",
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Suffix    => "suffix",
+            ConflictPolicy::Trash     => "trash",
+            ConflictPolicy::Ask       => "ask",
+            ConflictPolicy::Abort     => "abort",
+        }
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = ();
+    fn from_str(policy: &str) -> Result<ConflictPolicy, ()> {
+        match policy {
+            "skip"      => Ok(ConflictPolicy::Skip),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "suffix"    => Ok(ConflictPolicy::Suffix),
+            "trash"     => Ok(ConflictPolicy::Trash),
+            "ask"       => Ok(ConflictPolicy::Ask),
+            "abort"     => Ok(ConflictPolicy::Abort),
+            _           => Err(())
+        }
+    }
+}
+
+#[test]
+fn test_conflict_policy() {
+    for &policy in &[ConflictPolicy::Skip, ConflictPolicy::Overwrite, ConflictPolicy::Suffix, ConflictPolicy::Trash,
+        ConflictPolicy::Ask, ConflictPolicy::Abort]
+    {
+        assert_eq!(policy.as_str().parse::<ConflictPolicy>(), Ok(policy));
+    }
+    assert_eq!("rename".parse::<ConflictPolicy>(), Err(()));
+}
@@ -0,0 +1,74 @@
+use std::path::Path;
+
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use super::ReadDirError;
+
+/// The name of the files which list paths to leave out of a scan, in the syntax of `.gitignore`. Each applies to
+/// the directory it is in, and to the directories below it.
+pub const IGNORE_FILE: &'static str = ".tvrenamerignore";
+
+/// Directories which hold bonus material rather than episodes, and which are left out of every scan unless an
+/// ignore file includes them again with a `!` pattern. Hidden directories are also left out.
+const EXCLUDED: [&'static str; 3] = ["sample", "extras", "featurettes"];
+
+/// The ignore files in effect within a directory: those of the directory itself, and of every scanned directory
+/// above it, with the deepest last.
+#[derive(Clone, Default)]
+pub struct Rules {
+    layers: Vec<Gitignore>,
+}
+
+impl Rules {
+    /// Adds the rules of the ignore file of a directory which is being entered, if it has one.
+    pub fn enter(&self, directory: &Path) -> Result<Rules, ReadDirError> {
+        let path = directory.join(IGNORE_FILE);
+        if !path.is_file() { return Ok(self.clone()) }
+
+        let mut builder = GitignoreBuilder::new(directory);
+        if let Some(why) = builder.add(&path) {
+            return Err(ReadDirError::IgnoreFile(path, why.to_string()));
+        }
+        let gitignore = builder.build().map_err(|why| ReadDirError::IgnoreFile(path, why.to_string()))?;
+
+        let mut rules = self.clone();
+        rules.layers.push(gitignore);
+        Ok(rules)
+    }
+
+    /// Whether a path is left out of the scan. As in git, the deepest ignore file with a matching pattern decides,
+    /// and the last matching pattern within that file.
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        for layer in self.layers.iter().rev() {
+            match layer.matched(path, is_dir) {
+                Match::Ignore(_)    => return true,
+                Match::Whitelist(_) => return false,
+                Match::None         => ()
+            }
+        }
+
+        is_dir && path.file_name().map_or(false, |name| {
+            let name = name.to_string_lossy().to_lowercase();
+            name.starts_with('.') || EXCLUDED.contains(&name.as_str())
+        })
+    }
+}
+
+#[test]
+fn test_rules() {
+    use std::fs;
+    use backend::testing::TempDir;
+
+    let directory = TempDir::new("exclude");
+    fs::write(directory.join(IGNORE_FILE), "*.part\n/Trailers/\n!Extras/\n").unwrap();
+
+    let rules = Rules::default().enter(&directory).unwrap();
+    assert!(rules.is_excluded(&directory.join("Episode 1.mkv.part"), false));
+    assert!(rules.is_excluded(&directory.join("Trailers"), true));
+    assert!(rules.is_excluded(&directory.join("Sample"), true));
+    assert!(rules.is_excluded(&directory.join(".hidden"), true));
+    assert!(!rules.is_excluded(&directory.join("Extras"), true));
+    assert!(!rules.is_excluded(&directory.join("Disc 2"), true));
+    assert!(!Rules::default().is_excluded(&directory.join("Trailers"), true));
+}
@@ -0,0 +1,202 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use super::action::Action;
+use super::plan::{Operation, RenamePlan};
+
+/// The value of the action column for files that are moved into the Trash before anything is renamed.
+const TRASH: &'static str = "trash";
+
+/// The header row of a plan in the CSV format.
+const HEADER: [&'static str; 7] = ["series", "action", "source", "target", "series_id", "season", "episode"];
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum ExportError {
+        Io(err: io::Error) {
+            from()
+            display("unable to access the plan: {}", err)
+        }
+        Json(err: serde_json::Error) {
+            from()
+            display("the plan is not valid JSON: {}", err)
+        }
+        Csv(row: usize, reason: &'static str) {
+            display("row {} of the plan is invalid: {}", row, reason)
+        }
+    }
+}
+
+/// The formats that a plan may be written in, which is decided by the extension of the plan's file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format { Json, Csv }
+
+impl Format {
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => Format::Csv,
+            _ => Format::Json
+        }
+    }
+}
+
+/// Writes the plan to the given file, as CSV if the file ends with `.csv`, and as JSON otherwise.
+pub fn save(plan: &RenamePlan, path: &Path) -> Result<(), ExportError> {
+    let mut file = BufWriter::new(File::create(path)?);
+    match Format::from_path(path) {
+        Format::Json => serde_json::to_writer_pretty(&mut file, plan)?,
+        Format::Csv  => file.write_all(to_csv(plan).as_bytes())?
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// Reads a plan that was previously written by `save`, and possibly edited since.
+pub fn load(path: &Path) -> Result<RenamePlan, ExportError> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    match Format::from_path(path) {
+        Format::Json => Ok(serde_json::from_str(&contents)?),
+        Format::Csv  => from_csv(&contents)
+    }
+}
+
+/// Writes one row per operation, with the files to be moved into the Trash first. Their action is `trash`, and
+/// their target is the file to be trashed.
+fn to_csv(plan: &RenamePlan) -> String {
+    let mut output = String::new();
+    push_row(&mut output, &HEADER.iter().map(|&field| field.to_owned()).collect::<Vec<_>>());
+
+    let displaced = plan.displaced.iter().map(|operation| (TRASH, operation));
+    let operations = plan.operations.iter().map(|operation| (plan.action.as_str(), operation));
+    for (action, operation) in displaced.chain(operations) {
+        push_row(&mut output, &[
+            plan.series_name.clone(),
+            action.to_owned(),
+            operation.source.to_string_lossy().into_owned(),
+            operation.target.to_string_lossy().into_owned(),
+            operation.series_id.to_string(),
+            operation.season_no.to_string(),
+            operation.episode_no.to_string(),
+        ]);
+    }
+    output
+}
+
+fn from_csv(contents: &str) -> Result<RenamePlan, ExportError> {
+    let rows = parse_csv(contents)?;
+    let mut rows = rows.into_iter().enumerate().map(|(id, row)| (id + 1, row));
+    match rows.next() {
+        Some((_, ref header)) if header.iter().map(|field| field.as_str()).eq(HEADER.iter().cloned()) => (),
+        _ => return Err(ExportError::Csv(1, "the header does not match"))
+    }
+
+    let mut plan = RenamePlan::default();
+    let mut action = None;
+    for (id, row) in rows {
+        if row.len() != HEADER.len() { return Err(ExportError::Csv(id, "expected seven fields")); }
+        let operation = Operation {
+            source:     PathBuf::from(&row[2]),
+            target:     PathBuf::from(&row[3]),
+            series_id:  row[4].parse::<u32>().map_err(|_| ExportError::Csv(id, "the series ID is not a number"))?,
+            season_no:  row[5].parse::<u16>().map_err(|_| ExportError::Csv(id, "the season is not a number"))?,
+            episode_no: row[6].parse::<u32>().map_err(|_| ExportError::Csv(id, "the episode is not a number"))?,
+        };
+
+        plan.series_name = row[0].clone();
+        if row[1] == TRASH {
+            plan.displaced.push(operation);
+            continue
+        }
+
+        let row_action = row[1].parse::<Action>().map_err(|_| ExportError::Csv(id, "unknown action"))?;
+        if action.map_or(false, |action| action != row_action) {
+            return Err(ExportError::Csv(id, "every operation must have the same action"));
+        }
+        action = Some(row_action);
+        plan.push(operation);
+    }
+
+    plan.action = action.unwrap_or_default();
+    Ok(plan)
+}
+
+/// Appends a row, quoting any field that contains a comma, quote or line break.
+fn push_row(output: &mut String, fields: &[String]) {
+    for (id, field) in fields.iter().enumerate() {
+        if id != 0 { output.push(','); }
+        if field.contains(&[',', '"', '\n', '\r'][..]) {
+            output.push('"');
+            output.push_str(&field.replace('"', "\"\""));
+            output.push('"');
+        } else {
+            output.push_str(field);
+        }
+    }
+    output.push('\n');
+}
+
+/// Splits RFC 4180 CSV into rows of fields, skipping blank lines.
+fn parse_csv(contents: &str) -> Result<Vec<Vec<String>>, ExportError> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut characters = contents.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        match character {
+            '"' if quoted => if characters.peek() == Some(&'"') {
+                field.push('"');
+                let _ = characters.next();
+            } else {
+                quoted = false;
+            },
+            '"' if field.is_empty() => quoted = true,
+            '\n' if quoted => field.push('\n'),
+            ',' if !quoted => row.push(field.split_off(0)),
+            '\r' if !quoted && characters.peek() == Some(&'\n') => (),
+            '\n' => {
+                if row.is_empty() && field.is_empty() { continue }
+                row.push(field.split_off(0));
+                rows.push(row.split_off(0));
+            },
+            character => field.push(character)
+        }
+    }
+
+    if quoted { return Err(ExportError::Csv(rows.len() + 1, "a quoted field is not terminated")); }
+    if !row.is_empty() || !field.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[test]
+fn test_csv() {
+    let mut plan = RenamePlan::new(Action::Copy);
+    plan.series_name = "Show, The".to_owned();
+    plan.push(Operation {
+        source: PathBuf::from("/tv/a \"b\".mkv"), target: PathBuf::from("/tv/Show, The - 1x01.mkv"),
+        series_id: 1, season_no: 1, episode_no: 1
+    });
+    plan.displaced.push(Operation {
+        source: PathBuf::from("/tv/c.mkv"), target: PathBuf::from("/tv/Show, The - 1x02.mkv"),
+        series_id: 1, season_no: 1, episode_no: 2
+    });
+
+    let csv = to_csv(&plan);
+    assert_eq!(csv, "series,action,source,target,series_id,season,episode\n\
+        \"Show, The\",trash,/tv/c.mkv,\"/tv/Show, The - 1x02.mkv\",1,1,2\n\
+        \"Show, The\",copy,\"/tv/a \"\"b\"\".mkv\",\"/tv/Show, The - 1x01.mkv\",1,1,1\n");
+
+    let parsed = from_csv(&csv).unwrap();
+    assert_eq!(parsed.series_name, plan.series_name);
+    assert_eq!(parsed.action, Action::Copy);
+    assert_eq!(parsed.operations, plan.operations);
+    assert_eq!(parsed.displaced, plan.displaced);
+}
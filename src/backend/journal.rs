@@ -0,0 +1,322 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json;
+
+use super::action::Action;
+use super::plan::{Operation, Outcome, PlanError, RenamePlan};
+use super::trash::{self, Trashed};
+
+/// Name of the provider whose identifiers are recorded in each journal entry.
+pub const PROVIDER: &'static str = "tvdb";
+
+/// The extension given to journals of batches which have been reverted.
+const UNDONE: &'static str = "undone";
+
+/// The number of batches begun by this process, which distinguishes batches that begin at the same instant.
+static BATCHES: AtomicUsize = AtomicUsize::new(0);
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum JournalError {
+        NoStateDir {
+            display("unable to locate the state directory: neither $XDG_STATE_HOME nor $HOME are set")
+        }
+        Io(err: io::Error) {
+            from()
+            display("unable to access the rename journal: {}", err)
+        }
+        Format(err: serde_json::Error) {
+            from()
+            display("the rename journal is corrupted: {}", err)
+        }
+        NothingToUndo {
+            display("there are no renames left to undo")
+        }
+        BatchNotFound(id: String) {
+            display("no rename batch with the ID `{}` was found", id)
+        }
+        InvalidBatchId(id: String) {
+            display("`{}` is not the ID of a rename batch", id)
+        }
+        TargetMissing(path: PathBuf) {
+            display("{:?} no longer exists; refusing to undo", path)
+        }
+        TargetChanged(path: PathBuf) {
+            display("{:?} has changed since it was renamed; refusing to undo", path)
+        }
+        SourceOccupied(path: PathBuf) {
+            display("{:?} already exists; refusing to undo", path)
+        }
+        TrashMissing(path: PathBuf) {
+            display("{:?} is no longer in the Trash; refusing to undo", path)
+        }
+        RevertFailed(err: PlanError) {
+            from()
+            display("unable to undo: {}", err)
+        }
+        RemoveFailed(path: PathBuf, err: io::Error) {
+            display("unable to remove {:?}: {}", path, err)
+        }
+        RestoreFailed(path: PathBuf, err: io::Error) {
+            display("unable to restore {:?} from the Trash: {}", path, err)
+        }
+    }
+}
+
+/// A file that was renamed as part of a batch, along with the provider IDs that produced its new name.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub source:     PathBuf,
+    pub target:     PathBuf,
+    pub series_id:  u32,
+    pub season_no:  u16,
+    pub episode_no: u32,
+    /// Size of the target immediately after it was renamed.
+    pub size:       u64,
+    /// Modification time of the target, in seconds since the UNIX epoch, immediately after it was renamed.
+    pub modified:   u64,
+}
+
+/// Every rename performed by a single invocation of the program.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Batch {
+    pub id:          String,
+    pub timestamp:   u64,
+    pub series_name: String,
+    pub provider:    String,
+    /// How each file was placed at its new name. Journals written before actions existed are renames.
+    #[serde(default)]
+    pub action:      Action,
+    pub entries:     Vec<Entry>,
+    /// Files which were moved into the Trash to make way for the renames, which are restored when the batch is undone.
+    #[serde(default)]
+    pub trashed:     Vec<Trashed>,
+    /// Files which were replaced by the renames, and which cannot be restored.
+    #[serde(default)]
+    pub overwritten: Vec<PathBuf>,
+}
+
+impl Batch {
+    /// Begins a new batch for the given series. Nothing is written until a plan is recorded. The ID of the batch
+    /// is unique, even among the batches of every series that a library run renames within the same second.
+    pub fn new(series_name: &str) -> Batch {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let number = BATCHES.fetch_add(1, Ordering::SeqCst);
+        Batch {
+            id:          format!("{}-{:09}-{}-{}", time.as_secs(), time.subsec_nanos(), process::id(), number),
+            timestamp:   time.as_secs(),
+            series_name: series_name.to_owned(),
+            provider:    PROVIDER.to_owned(),
+            action:      Action::default(),
+            entries:     Vec::new(),
+            trashed:     Vec::new(),
+            overwritten: Vec::new(),
+        }
+    }
+
+    /// Records every rename of a committed plan, along with the files that it moved out of the way, and writes the
+    /// journal to disk.
+    pub fn record(&mut self, plan: &RenamePlan, outcome: Outcome) -> Result<(), JournalError> {
+        self.action = plan.action;
+        self.trashed = outcome.trashed;
+        self.overwritten = outcome.overwritten;
+        for operation in &plan.operations {
+            let metadata = fs::metadata(&operation.target)?;
+            self.entries.push(Entry {
+                source:     operation.source.clone(),
+                target:     operation.target.clone(),
+                series_id:  operation.series_id,
+                season_no:  operation.season_no,
+                episode_no: operation.episode_no,
+                size:       metadata.len(),
+                modified:   modified(&metadata),
+            });
+        }
+        self.save()
+    }
+
+    /// Writes the batch to `$XDG_STATE_HOME/tv-renamer/<id>.json`.
+    pub fn save(&self) -> Result<(), JournalError> {
+        self.write(&state_directory()?)
+    }
+
+    /// Writes the batch to the given directory, refusing to replace the journal of another batch.
+    fn write(&self, directory: &Path) -> Result<(), JournalError> {
+        fs::create_dir_all(directory)?;
+        let file = OpenOptions::new().write(true).create_new(true).open(directory.join([&self.id, ".json"].concat()))?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Ensures that every renamed file is still where it was left and is unmodified, and that nothing has since
+    /// taken the place of the original file names, other than files renamed by the same batch. The original files of
+    /// copies and links are expected to still exist, as are the files which the batch moved into the Trash.
+    pub fn verify(&self) -> Result<(), JournalError> {
+        if let Some(trashed) = self.trashed.iter().find(|trashed| !trashed.file.exists()) {
+            return Err(JournalError::TrashMissing(trashed.original.clone()));
+        }
+        for entry in &self.entries {
+            let metadata = fs::metadata(&entry.target)
+                .map_err(|_| JournalError::TargetMissing(entry.target.clone()))?;
+            if metadata.len() != entry.size || modified(&metadata) != entry.modified {
+                return Err(JournalError::TargetChanged(entry.target.clone()));
+            }
+            if self.action.vacates_source() && entry.source.exists()
+                && !self.entries.iter().any(|other| other.target == entry.source)
+            {
+                return Err(JournalError::SourceOccupied(entry.source.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reverts every rename in the batch as a single transaction, or removes every copy and link that it created,
+    /// restores the files that it moved into the Trash, and marks the batch as undone. Nothing is touched unless the
+    /// batch first passes verification. Files which the batch overwrote cannot be restored.
+    pub fn undo(&self) -> Result<usize, JournalError> {
+        self.verify()?;
+        if self.action.vacates_source() {
+            let mut plan = RenamePlan::new(self.action);
+            for entry in self.entries.iter().rev() {
+                plan.push(Operation {
+                    source:     entry.target.clone(),
+                    target:     entry.source.clone(),
+                    series_id:  entry.series_id,
+                    season_no:  entry.season_no,
+                    episode_no: entry.episode_no,
+                });
+            }
+            plan.execute()?;
+        } else {
+            for entry in self.entries.iter().rev() {
+                self.action.revert(&entry.source, &entry.target)
+                    .map_err(|why| JournalError::RemoveFailed(entry.target.clone(), why))?;
+            }
+        }
+
+        // The trashed files had the names that the batch renamed files to, which have now been vacated.
+        for trashed in self.trashed.iter().rev() {
+            trash::restore(trashed).map_err(|why| JournalError::RestoreFailed(trashed.original.clone(), why))?;
+        }
+
+        let journal = state_directory()?.join([&self.id, ".json"].concat());
+        fs::rename(&journal, journal.with_extension(UNDONE))?;
+        Ok(self.entries.len())
+    }
+}
+
+/// Loads the batch with the given ID, or the most recent batch which has not been undone if no ID is given.
+pub fn load(id: Option<&str>) -> Result<Batch, JournalError> {
+    // IDs are checked before they name a file, so that they cannot lead out of the state directory.
+    if let Some(id) = id.filter(|id| started(id).is_none()) {
+        return Err(JournalError::InvalidBatchId(id.to_owned()));
+    }
+
+    let directory = state_directory()?;
+    let path = match id {
+        Some(id) => {
+            let path = directory.join([id, ".json"].concat());
+            if !path.exists() { return Err(JournalError::BatchNotFound(id.to_owned())); }
+            path
+        },
+        None => latest(&directory)?.ok_or(JournalError::NothingToUndo)?
+    };
+
+    Ok(serde_json::from_reader(File::open(path)?)?)
+}
+
+/// Locates the journal of the most recent batch that has not been undone.
+fn latest(directory: &Path) -> Result<Option<PathBuf>, JournalError> {
+    if !directory.exists() { return Ok(None) }
+    let mut latest: Option<((u64, u64, u64), PathBuf)> = None;
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().map_or(true, |extension| extension != "json") { continue }
+        if let Some(timestamp) = path.file_stem().and_then(|stem| stem.to_str()).and_then(started) {
+            if latest.as_ref().map_or(true, |&(newest, _)| timestamp >= newest) {
+                latest = Some((timestamp, path));
+            }
+        }
+    }
+    Ok(latest.map(|(_, path)| path))
+}
+
+/// When the batch with the given ID was started, in seconds and nanoseconds, along with the number of the batch
+/// within its process, by which batches are ordered. Batch IDs take the form of `seconds-nanoseconds-pid-number`, but
+/// journals of earlier versions give the seconds and process ID alone. Anything else is not a batch ID.
+fn started(id: &str) -> Option<(u64, u64, u64)> {
+    let fields = id.split('-').map(|field| field.parse::<u64>().ok()).collect::<Option<Vec<u64>>>()?;
+    match *fields.as_slice() {
+        [seconds, nanos, _, number] => Some((seconds, nanos, number)),
+        [seconds, _]                => Some((seconds, 0, 0)),
+        _                           => None
+    }
+}
+
+/// The directory where rename journals are stored: `$XDG_STATE_HOME/tv-renamer`, which defaults to
+/// `~/.local/state/tv-renamer`.
+pub fn state_directory() -> Result<PathBuf, JournalError> {
+    env::var_os("XDG_STATE_HOME").map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::home_dir().map(|home| home.join(".local/state")))
+        .map(|state| state.join("tv-renamer"))
+        .ok_or(JournalError::NoStateDir)
+}
+
+/// The modification time of a file in seconds since the UNIX epoch.
+fn modified(metadata: &fs::Metadata) -> u64 {
+    metadata.modified().ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_secs())
+}
+
+#[test]
+fn test_batch_ids() {
+    use backend::testing::TempDir;
+
+    // Library runs commit the batches of several series in quick succession.
+    let directory = TempDir::new("journal");
+    let (first, second) = (Batch::new("Firefly"), Batch::new("The Wire"));
+    assert!(first.id != second.id);
+    first.write(&directory).unwrap();
+    second.write(&directory).unwrap();
+    assert!(first.write(&directory).is_err());
+    assert_eq!(latest(&directory).unwrap(), Some(directory.join([&second.id, ".json"].concat())));
+    assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+
+    assert_eq!(started("1700000000-000000042-1234-7"), Some((1700000000, 42, 7)));
+    assert_eq!(started("1700000000-1234"), Some((1700000000, 0, 0)));
+    assert_eq!(started("../../1700000000-1234"), None);
+    assert!(started(&second.id).is_some());
+    match load(Some("../../x")) {
+        Err(JournalError::InvalidBatchId(_)) => (),
+        _ => panic!("expected the ID to be refused")
+    }
+}
+
+#[test]
+fn test_verify_trashed() {
+    use backend::testing::TempDir;
+
+    // A batch cannot be undone once a file that it moved into the Trash has been emptied from it.
+    let directory = TempDir::new("trashed");
+    let mut batch = Batch::new("Firefly");
+    batch.trashed.push(Trashed {
+        original: directory.join("Firefly - 1x01.mkv"), file: directory.join("files/Firefly - 1x01.mkv"),
+        info: directory.join("info/Firefly - 1x01.mkv.trashinfo")
+    });
+    match batch.verify() {
+        Err(JournalError::TrashMissing(path)) => assert_eq!(path, directory.join("Firefly - 1x01.mkv")),
+        _ => panic!("expected the trashed file to be missing")
+    }
+
+    fs::create_dir_all(directory.join("files")).unwrap();
+    fs::write(directory.join("files/Firefly - 1x01.mkv"), b"").unwrap();
+    assert!(batch.verify().is_ok());
+}
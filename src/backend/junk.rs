@@ -0,0 +1,166 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::media;
+
+/// Files which are smaller than this fraction of the median size of the videos of their season are not episodes.
+const SIZE_RATIO: u64 = 5;
+
+/// Files which are shorter than this fraction of the median duration of the videos of their season are not episodes.
+/// Episodes of a season rarely differ in length by more than a double episode does.
+const DURATION_RATIO: u32 = 3;
+
+/// The fewest videos in a season for their sizes or durations to be compared, as the median of fewer says little.
+const MEDIAN_MINIMUM: usize = 3;
+
+/// Determines what becomes of the videos of a season which do not appear to be episodes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JunkPolicy {
+    /// They are left out of the season, and are listed by previews.
+    Exclude,
+    /// They are numbered along with the episodes, and are reported as problems before anything is renamed.
+    Flag,
+}
+
+impl FromStr for JunkPolicy {
+    type Err = ();
+    fn from_str(policy: &str) -> Result<JunkPolicy, ()> {
+        match policy {
+            "exclude" => Ok(JunkPolicy::Exclude),
+            "flag"    => Ok(JunkPolicy::Flag),
+            _         => Err(())
+        }
+    }
+}
+
+/// Why a video does not appear to be an episode.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JunkKind {
+    /// A short excerpt of an episode, as included with scene releases, such as `show.s01e01.sample.mkv`.
+    Sample,
+    /// A trailer, teaser or promo for the series.
+    Trailer,
+    /// Bonus material, such as a featurette or bloopers.
+    Bonus,
+    /// A file much smaller than the other videos of its season.
+    Small,
+    /// A file much shorter than the other videos of its season, by the durations given by their containers.
+    Short,
+}
+
+impl JunkKind {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            JunkKind::Sample  => "sample",
+            JunkKind::Trailer => "trailer",
+            JunkKind::Bonus   => "bonus",
+            JunkKind::Small   => "small",
+            JunkKind::Short   => "short",
+        }
+    }
+
+    /// Describes why the video is not an episode, as shown in a preview.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            JunkKind::Sample  => "named as a sample",
+            JunkKind::Trailer => "named as a trailer",
+            JunkKind::Bonus   => "named as bonus material",
+            JunkKind::Small   => "much smaller than the other videos of its season",
+            JunkKind::Short   => "much shorter than the other videos of its season",
+        }
+    }
+}
+
+/// A video of a season which does not appear to be an episode.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Junk {
+    pub path: PathBuf,
+    pub kind: JunkKind,
+}
+
+/// Finds the videos of a season which do not appear to be episodes, by their names, and by their sizes relative to
+/// the other videos of the season. When `durations` is set, their durations are compared as well, which requires
+/// reading the headers of every video.
+pub fn detect(videos: &[PathBuf], durations: bool) -> Vec<Junk> {
+    let sizes = videos.iter()
+        .map(|path| fs::metadata(path).map(|metadata| metadata.len()).ok())
+        .collect::<Vec<Option<u64>>>();
+    let lengths = videos.iter()
+        .map(|path| if durations { media::inspect(path).ok().and_then(|info| info.duration) } else { None })
+        .collect::<Vec<Option<Duration>>>();
+    let (median_size, median_length) = (median(&sizes), median(&lengths));
+
+    videos.iter().zip(sizes).zip(lengths)
+        .filter_map(|((path, size), length)| {
+            let small = match (median_size, size) {
+                (Some(median), Some(size)) => size < median / SIZE_RATIO,
+                _ => false
+            };
+            let short = match (median_length, length) {
+                (Some(median), Some(length)) => length < median / DURATION_RATIO,
+                _ => false
+            };
+            named(path)
+                .or(if small { Some(JunkKind::Small) } else { None })
+                .or(if short { Some(JunkKind::Short) } else { None })
+                .map(|kind| Junk { path: path.clone(), kind: kind })
+        })
+        .collect()
+}
+
+/// The median of the sizes or durations of the videos for which they are known, if there are enough of them.
+fn median<T: Copy + Ord>(values: &[Option<T>]) -> Option<T> {
+    let mut known = values.iter().filter_map(|&value| value).collect::<Vec<T>>();
+    if known.len() < MEDIAN_MINIMUM { return None }
+    known.sort();
+    Some(known[known.len() / 2])
+}
+
+/// Recognizes junk by the words of its file name, such as `sample` in `show.s01e01-sample.mkv`.
+fn named(path: &Path) -> Option<JunkKind> {
+    let name = path.file_stem().map(|stem| stem.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let words = name.split(|character: char| !character.is_alphanumeric()).collect::<Vec<_>>();
+    let has = |candidates: &[&str]| words.iter().any(|word| candidates.contains(word));
+    if has(&["sample"]) {
+        Some(JunkKind::Sample)
+    } else if has(&["trailer", "trailers", "teaser", "promo"]) {
+        Some(JunkKind::Trailer)
+    } else if has(&["featurette", "featurettes", "bloopers"]) || name.contains("behind the scenes") {
+        Some(JunkKind::Bonus)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_detect() {
+    use backend::testing::TempDir;
+
+    assert_eq!(named(Path::new("show.s01e01.720p-sample.mkv")), Some(JunkKind::Sample));
+    assert_eq!(named(Path::new("Show - Official Trailer.mp4")), Some(JunkKind::Trailer));
+    assert_eq!(named(Path::new("Show - Behind the Scenes.mkv")), Some(JunkKind::Bonus));
+    assert_eq!(named(Path::new("Show - S01E01 - Samples of Grace.mkv")), None);
+
+    let directory = TempDir::new("junk");
+    let videos = ["a.mkv", "b.mkv", "c.mkv", "d.mkv"].iter().map(|name| directory.join(name)).collect::<Vec<_>>();
+    for (path, &size) in videos.iter().zip(&[1000, 1100, 40, 1200]) {
+        fs::write(path, vec![0u8; size]).unwrap();
+    }
+    assert_eq!(detect(&videos, false), vec![Junk { path: directory.join("c.mkv"), kind: JunkKind::Small }]);
+    assert_eq!(detect(&videos[..2], false), Vec::new());
+
+    // Matroska files of the same size, whose `Info` elements give their durations in milliseconds, are only compared
+    // by those durations when asked to be.
+    let matroska = |minutes: f64| [&[
+        0x1A, 0x45, 0xDF, 0xA3, 0x80, 0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        0x15, 0x49, 0xA9, 0x66, 0x8B, 0x44, 0x89, 0x88
+    ][..], &(minutes * 60_000.0).to_bits().to_be_bytes()].concat();
+    for (path, &minutes) in videos.iter().zip(&[22.0, 21.5, 1.0, 23.0]) {
+        fs::write(path, matroska(minutes)).unwrap();
+    }
+    assert_eq!(detect(&videos, false), Vec::new());
+    assert_eq!(detect(&videos, true), vec![Junk { path: directory.join("c.mkv"), kind: JunkKind::Short }]);
+}
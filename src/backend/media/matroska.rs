@@ -0,0 +1,265 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::time::Duration;
+
+use super::{read_body, transfer_hdr, uint, MediaInfo};
+
+const EBML:                     u32 = 0x1A45DFA3;
+const SEGMENT:                  u32 = 0x18538067;
+const INFO:                     u32 = 0x1549A966;
+const TIMESTAMP_SCALE:          u32 = 0x2AD7B1;
+const DURATION:                 u32 = 0x4489;
+const TRACKS:                   u32 = 0x1654AE6B;
+const TRACK_ENTRY:              u32 = 0xAE;
+const TRACK_TYPE:               u32 = 0x83;
+const CODEC_ID:                 u32 = 0x86;
+const BLOCK_ADDITION_MAPPING:   u32 = 0x41E4;
+const BLOCK_ADD_ID_TYPE:        u32 = 0x41E7;
+const VIDEO:                    u32 = 0xE0;
+const PIXEL_WIDTH:              u32 = 0xB0;
+const PIXEL_HEIGHT:             u32 = 0xBA;
+const COLOUR:                   u32 = 0x55B0;
+const TRANSFER_CHARACTERISTICS: u32 = 0x55BA;
+const AUDIO:                    u32 = 0xE1;
+const CHANNELS:                 u32 = 0x9F;
+
+const TRACK_VIDEO: u64 = 1;
+const TRACK_AUDIO: u64 = 2;
+
+/// The block additions of Dolby Vision, whose configuration is named `dvcC` or `dvvC`.
+const DOLBY_VISION: [u64; 2] = [0x64766343, 0x64767643];
+
+/// Reads the properties of a Matroska or WebM video from the `Info` and `Tracks` elements of its segment, which precede
+/// the clusters of frames in practically every file. The elements between them are skipped.
+pub fn inspect<R: Read + Seek>(input: &mut R) -> io::Result<MediaInfo> {
+    match header(input)? {
+        Some((EBML, Some(size))) => { input.seek(SeekFrom::Current(size as i64))?; },
+        _ => return Err(invalid())
+    }
+    match header(input)? {
+        Some((SEGMENT, _)) => (),
+        _ => return Err(invalid())
+    }
+
+    let mut info = MediaInfo::default();
+    let (mut has_info, mut has_tracks) = (false, false);
+    while !(has_info && has_tracks) {
+        match header(input)? {
+            Some((INFO, Some(size))) => {
+                read_info(&read_body(input, size)?, &mut info);
+                has_info = true;
+            },
+            Some((TRACKS, Some(size))) => {
+                read_tracks(&read_body(input, size)?, &mut info);
+                has_tracks = true;
+            },
+            Some((_, Some(size))) => { input.seek(SeekFrom::Current(size as i64))?; },
+            // An element of unknown size, which is usually a cluster being streamed, can only be skipped by reading
+            // every element within it.
+            Some((_, None)) | None => break
+        }
+    }
+    Ok(info)
+}
+
+fn read_info(body: &[u8], info: &mut MediaInfo) {
+    let mut scale = 1_000_000;
+    let mut duration = None;
+    for (id, data) in Elements(body) {
+        match id {
+            TIMESTAMP_SCALE => scale = uint(data),
+            DURATION        => duration = float(data),
+            _               => ()
+        }
+    }
+
+    // The duration is counted in units of the timestamp scale, which are nanoseconds.
+    info.duration = duration.filter(|duration| duration.is_finite() && *duration >= 0.0)
+        .map(|duration| Duration::from_nanos((duration * scale as f64) as u64));
+}
+
+fn read_tracks(body: &[u8], info: &mut MediaInfo) {
+    let (mut has_video, mut has_audio) = (false, false);
+    for (_, entry) in Elements(body).filter(|&(id, _)| id == TRACK_ENTRY) {
+        let (mut kind, mut codec) = (0, "");
+        for (id, data) in Elements(entry) {
+            match id {
+                TRACK_TYPE => kind = uint(data),
+                CODEC_ID   => codec = ::std::str::from_utf8(data).unwrap_or("").trim_end_matches('\0'),
+                _          => ()
+            }
+        }
+
+        // Only the first track of each type is described.
+        if kind == TRACK_VIDEO && !has_video {
+            has_video = true;
+            info.video_codec = video_codec(codec);
+            for (id, data) in Elements(entry) {
+                match id {
+                    VIDEO => read_video(data, info),
+                    BLOCK_ADDITION_MAPPING => {
+                        let dolby_vision = Elements(data)
+                            .any(|(id, data)| id == BLOCK_ADD_ID_TYPE && DOLBY_VISION.contains(&uint(data)));
+                        if dolby_vision { info.hdr = Some("DV"); }
+                    },
+                    _ => ()
+                }
+            }
+        } else if kind == TRACK_AUDIO && !has_audio {
+            has_audio = true;
+            info.audio_codec = audio_codec(codec);
+            // Tracks which do not give their channels have one.
+            info.channels = Some(1);
+            for (_, audio) in Elements(entry).filter(|&(id, _)| id == AUDIO) {
+                for (_, data) in Elements(audio).filter(|&(id, _)| id == CHANNELS) {
+                    info.channels = Some(uint(data).min(255) as u8);
+                }
+            }
+        }
+    }
+}
+
+fn read_video(body: &[u8], info: &mut MediaInfo) {
+    for (id, data) in Elements(body) {
+        match id {
+            PIXEL_WIDTH  => info.width = Some(uint(data) as u32),
+            PIXEL_HEIGHT => info.height = Some(uint(data) as u32),
+            COLOUR if info.hdr.is_none() => {
+                info.hdr = Elements(data).filter(|&(id, _)| id == TRANSFER_CHARACTERISTICS)
+                    .filter_map(|(_, data)| transfer_hdr(uint(data)))
+                    .next();
+            },
+            _ => ()
+        }
+    }
+}
+
+/// Names a video codec by its Matroska codec ID.
+fn video_codec(codec: &str) -> Option<&'static str> {
+    match codec {
+        "V_MPEG4/ISO/AVC"  => Some("x264"),
+        "V_MPEGH/ISO/HEVC" => Some("x265"),
+        "V_AV1"            => Some("AV1"),
+        "V_VP9"            => Some("VP9"),
+        "V_VP8"            => Some("VP8"),
+        "V_MPEG2"          => Some("MPEG2"),
+        "V_THEORA"         => Some("Theora"),
+        _ if codec.starts_with("V_MPEG4/ISO/") => Some("XviD"),
+        _                  => None
+    }
+}
+
+/// Names an audio codec by its Matroska codec ID.
+fn audio_codec(codec: &str) -> Option<&'static str> {
+    match codec {
+        "A_AC3"     => Some("AC3"),
+        "A_EAC3"    => Some("EAC3"),
+        "A_TRUEHD"  => Some("TrueHD"),
+        "A_OPUS"    => Some("Opus"),
+        "A_VORBIS"  => Some("Vorbis"),
+        "A_FLAC"    => Some("FLAC"),
+        "A_MPEG/L3" => Some("MP3"),
+        "A_MPEG/L2" => Some("MP2"),
+        _ if codec.starts_with("A_AAC") => Some("AAC"),
+        _ if codec.starts_with("A_DTS") => Some("DTS"),
+        _ if codec.starts_with("A_PCM") => Some("PCM"),
+        _           => None
+    }
+}
+
+/// The elements within the body of a master element, as their IDs and bodies. Iteration ends at the first element
+/// which is truncated, or whose size is unknown.
+struct Elements<'a>(&'a [u8]);
+
+impl<'a> Iterator for Elements<'a> {
+    type Item = (u32, &'a [u8]);
+
+    fn next(&mut self) -> Option<(u32, &'a [u8])> {
+        let (id, id_length) = vint(self.0, true)?;
+        let id = id?;
+        let (size, size_length) = vint(&self.0[id_length..], false)?;
+        let start = id_length + size_length;
+        let end = size.filter(|&size| size <= (self.0.len() - start) as u64)? as usize + start;
+        let body = &self.0[start..end];
+        self.0 = &self.0[end..];
+        Some((id as u32, body))
+    }
+}
+
+/// Reads a variable length integer of EBML, and returns its value along with its length. IDs keep their length marker,
+/// and sizes whose bits are all set are unknown.
+fn vint(data: &[u8], is_id: bool) -> Option<(Option<u64>, usize)> {
+    let first = *data.first()?;
+    if first == 0 { return None }
+    let length = first.leading_zeros() as usize + 1;
+    if data.len() < length || (is_id && length > 4) { return None }
+
+    let marker = if is_id { 0xFF } else { (0xFFu16 >> length) as u8 };
+    let value = data[1..length].iter().fold((first & marker) as u64, |value, &byte| value << 8 | byte as u64);
+    let unknown = !is_id && value == (1 << (7 * length)) - 1;
+    Some((if unknown { None } else { Some(value) }, length))
+}
+
+/// Reads the ID and size of the next element of the input, or nothing at its end.
+fn header<R: Read>(input: &mut R) -> io::Result<Option<(u32, Option<u64>)>> {
+    let mut bytes = [0u8; 12];
+    if input.read(&mut bytes[..1])? == 0 { return Ok(None) }
+    let id_length = bytes[0].leading_zeros() as usize + 1;
+    if id_length > 4 { return Err(invalid()) }
+    input.read_exact(&mut bytes[1..id_length + 1])?;
+    let size_length = bytes[id_length].leading_zeros() as usize + 1;
+    if size_length > 8 { return Err(invalid()) }
+    input.read_exact(&mut bytes[id_length + 1..id_length + size_length])?;
+
+    let (id, _) = vint(&bytes, true).ok_or_else(invalid)?;
+    let (size, _) = vint(&bytes[id_length..], false).ok_or_else(invalid)?;
+    Ok(Some((id.unwrap_or(0) as u32, size)))
+}
+
+fn float(data: &[u8]) -> Option<f64> {
+    match data.len() {
+        4 => Some(f32::from_bits(uint(data) as u32) as f64),
+        8 => Some(f64::from_bits(uint(data))),
+        _ => None
+    }
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "not a valid Matroska file")
+}
+
+#[test]
+fn test_inspect() {
+    use std::io::Cursor;
+
+    // Encodes an element whose body is small enough for a one byte size.
+    fn element(id: u32, body: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_be_bytes().iter().cloned().skip_while(|&byte| byte == 0).collect::<Vec<u8>>();
+        bytes.push(0x80 | body.len() as u8);
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    let video = [element(PIXEL_WIDTH, &[0x07, 0x80]), element(PIXEL_HEIGHT, &[0x04, 0x38]),
+        element(COLOUR, &element(TRANSFER_CHARACTERISTICS, &[16]))].concat();
+    let tracks = [
+        element(TRACK_ENTRY, &[element(TRACK_TYPE, &[1]), element(CODEC_ID, b"V_MPEGH/ISO/HEVC"),
+            element(VIDEO, &video)].concat()),
+        element(TRACK_ENTRY, &[element(TRACK_TYPE, &[2]), element(CODEC_ID, b"A_EAC3"),
+            element(AUDIO, &element(CHANNELS, &[6]))].concat()),
+    ].concat();
+    let info = [element(TIMESTAMP_SCALE, &[0x0F, 0x42, 0x40]),
+        element(DURATION, &2_562_000f64.to_bits().to_be_bytes())].concat();
+
+    // The segment is of unknown size, as when it is written by a live recording.
+    let mut file = element(EBML, &element(0x4282, b"matroska"));
+    file.extend_from_slice(&[0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    file.extend(element(0xEC, &[0; 16]));
+    file.extend(element(INFO, &info));
+    file.extend(element(TRACKS, &tracks));
+
+    let media = inspect(&mut Cursor::new(file)).unwrap();
+    assert_eq!((media.width, media.height), (Some(1920), Some(1080)));
+    assert_eq!((media.video_codec, media.audio_codec, media.channels), (Some("x265"), Some("EAC3"), Some(6)));
+    assert_eq!(media.hdr, Some("HDR10"));
+    assert_eq!(media.duration, Some(Duration::from_secs(2562)));
+}
@@ -0,0 +1,128 @@
+pub mod matroska;
+pub mod mp4;
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+/// The properties of a video file which are read from the headers of its container, for the media tokens of a
+/// template. Properties which the container does not give are `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MediaInfo {
+    pub width:       Option<u32>,
+    pub height:      Option<u32>,
+    /// The codec of the first video track, such as `x264`, `x265` or `AV1`.
+    pub video_codec: Option<&'static str>,
+    /// The codec of the first audio track, such as `AAC`, `AC3`, `EAC3` or `DTS`.
+    pub audio_codec: Option<&'static str>,
+    /// The number of channels of the first audio track, including any LFE channel.
+    pub channels:    Option<u8>,
+    /// The dynamic range of the video, when it is high: `DV` (Dolby Vision), `HDR10` or `HLG`.
+    pub hdr:         Option<&'static str>,
+    pub duration:    Option<Duration>,
+}
+
+impl MediaInfo {
+    /// The resolution as it is commonly written, such as `1080p`. Widescreen videos are classified by their width, so
+    /// that a 1920x800 video is `1080p`.
+    pub fn resolution(&self) -> Option<String> {
+        let (width, height) = (self.width.unwrap_or(0), self.height?);
+        let resolution = if width >= 3800 || height >= 2100 {
+            "2160p"
+        } else if width >= 1900 || height >= 1000 {
+            "1080p"
+        } else if width >= 1260 || height >= 700 {
+            "720p"
+        } else if height >= 560 {
+            "576p"
+        } else if height >= 470 {
+            "480p"
+        } else {
+            return Some(format!("{}p", height))
+        };
+        Some(resolution.to_owned())
+    }
+
+    /// The channel layout of the first audio track, such as `2.0` or `5.1`. Six or more channels are taken to include
+    /// an LFE channel.
+    pub fn channel_layout(&self) -> Option<String> {
+        match self.channels? {
+            0 => None,
+            channels if channels >= 6 => Some(format!("{}.1", channels - 1)),
+            channels => Some(format!("{}.0", channels))
+        }
+    }
+
+    /// The duration in whole minutes, such as `42m`.
+    pub fn minutes(&self) -> Option<String> {
+        self.duration.map(|duration| format!("{}m", (duration.as_secs() + 30) / 60))
+    }
+}
+
+/// Reads the properties of a Matroska, WebM or MP4 video from the headers of its container. Other containers have
+/// no properties.
+pub fn inspect(path: &Path) -> io::Result<MediaInfo> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut signature = [0u8; 8];
+    let length = read_up_to(&mut file, &mut signature)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    if signature[..length].starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        matroska::inspect(&mut file)
+    } else if length == 8 && (&signature[4..] == b"ftyp" || &signature[4..] == b"moov") {
+        mp4::inspect(&mut file)
+    } else {
+        Ok(MediaInfo::default())
+    }
+}
+
+/// Fills as much of the buffer as the input allows, returning how much was read.
+fn read_up_to<R: Read>(input: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut length = 0;
+    while length < buffer.len() {
+        match input.read(&mut buffer[length..])? {
+            0 => break,
+            read => length += read
+        }
+    }
+    Ok(length)
+}
+
+/// Reads the body of an element or box into memory, refusing those too large to be headers.
+fn read_body<R: Read>(input: &mut R, size: u64) -> io::Result<Vec<u8>> {
+    const LIMIT: u64 = 64 * 1024 * 1024;
+    if size > LIMIT {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the header of the container is too large"));
+    }
+    let mut body = vec![0; size as usize];
+    input.read_exact(&mut body)?;
+    Ok(body)
+}
+
+/// Interprets a big-endian unsigned integer of up to eight bytes.
+fn uint(bytes: &[u8]) -> u64 {
+    bytes.iter().take(8).fold(0, |value, &byte| value << 8 | byte as u64)
+}
+
+/// The dynamic range given by the transfer characteristics of ISO/IEC 23091-2, which both containers use.
+fn transfer_hdr(transfer: u64) -> Option<&'static str> {
+    match transfer {
+        16 => Some("HDR10"),
+        18 => Some("HLG"),
+        _  => None
+    }
+}
+
+#[test]
+fn test_media_info() {
+    let info = MediaInfo {
+        width: Some(1920), height: Some(800), channels: Some(6), duration: Some(Duration::from_secs(2562)),
+        ..MediaInfo::default()
+    };
+    assert_eq!(info.resolution(), Some(String::from("1080p")));
+    assert_eq!(info.channel_layout(), Some(String::from("5.1")));
+    assert_eq!(info.minutes(), Some(String::from("43m")));
+    assert_eq!(MediaInfo { height: Some(360), ..MediaInfo::default() }.resolution(), Some(String::from("360p")));
+    assert_eq!(MediaInfo { channels: Some(2), ..MediaInfo::default() }.channel_layout(), Some(String::from("2.0")));
+}
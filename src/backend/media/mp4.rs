@@ -0,0 +1,216 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::time::Duration;
+
+use super::{read_body, read_up_to, transfer_hdr, uint, MediaInfo};
+
+/// The number of channels of each audio coding mode of AC-3 and E-AC-3, excluding the LFE channel.
+const AC3_CHANNELS: [u8; 8] = [2, 1, 2, 3, 3, 4, 4, 5];
+
+/// Reads the properties of an MP4 or QuickTime video from its `moov` box, skipping the boxes before it, which may
+/// include the media data itself.
+pub fn inspect<R: Read + Seek>(input: &mut R) -> io::Result<MediaInfo> {
+    loop {
+        match header(input)? {
+            Some((ref kind, Some(size))) if kind == b"moov" => return Ok(read_movie(&read_body(input, size)?)),
+            Some((_, Some(size))) => { input.seek(SeekFrom::Current(size as i64))?; },
+            // The last box may extend to the end of the file, without giving its size.
+            Some((_, None)) | None => return Ok(MediaInfo::default())
+        }
+    }
+}
+
+fn read_movie(body: &[u8]) -> MediaInfo {
+    let mut info = MediaInfo::default();
+    for (kind, data) in Boxes(body) {
+        match &kind {
+            b"mvhd" => info.duration = movie_duration(data),
+            b"trak" => read_track(data, &mut info),
+            _       => ()
+        }
+    }
+    info
+}
+
+/// Reads the duration of a movie header, which counts units of its timescale.
+fn movie_duration(data: &[u8]) -> Option<Duration> {
+    let (timescale, duration) = match *data.first()? {
+        1 => (uint(data.get(20..24)?), uint(data.get(24..32)?)),
+        _ => (uint(data.get(12..16)?), uint(data.get(16..20)?))
+    };
+    // A duration whose bits are all set is unknown.
+    if timescale == 0 || duration == u32::MAX as u64 || duration == u64::MAX { return None }
+    let nanos = (duration % timescale) * 1_000_000_000 / timescale;
+    Some(Duration::new(duration / timescale, nanos as u32))
+}
+
+/// Reads the sample description of the first video track and the first audio track.
+fn read_track(body: &[u8], info: &mut MediaInfo) {
+    let media = match child(body, b"mdia") { Some(media) => media, None => return };
+    let handler = child(media, b"hdlr").and_then(|handler| handler.get(8..12)).unwrap_or_default();
+    let entry = child(media, b"minf")
+        .and_then(|information| child(information, b"stbl"))
+        .and_then(|table| child(table, b"stsd"))
+        .and_then(|description| description.get(8..))
+        .and_then(|entries| Boxes(entries).next());
+    let (kind, entry) = match entry { Some(entry) => entry, None => return };
+
+    match handler {
+        b"vide" if info.width.is_none() => read_video(&kind, entry, info),
+        b"soun" if info.channels.is_none() => read_audio(&kind, entry, info),
+        _ => ()
+    }
+}
+
+fn read_video(kind: &[u8; 4], entry: &[u8], info: &mut MediaInfo) {
+    info.width = entry.get(24..26).map(|width| uint(width) as u32);
+    info.height = entry.get(26..28).map(|height| uint(height) as u32);
+    info.video_codec = match kind {
+        b"avc1" | b"avc3" | b"dva1" | b"dvav" => Some("x264"),
+        b"hvc1" | b"hev1" | b"dvh1" | b"dvhe" => Some("x265"),
+        b"av01"                               => Some("AV1"),
+        b"vp09"                               => Some("VP9"),
+        b"vp08"                               => Some("VP8"),
+        b"mp4v"                               => Some("XviD"),
+        _                                     => None
+    };
+
+    // The boxes within a visual sample entry follow 78 bytes of fields.
+    let children = entry.get(78..).unwrap_or_default();
+    let dolby_vision = [&b"dva1"[..], b"dvav", b"dvh1", b"dvhe"].contains(&&kind[..])
+        || Boxes(children).any(|(kind, _)| &kind == b"dvcC" || &kind == b"dvvC");
+    info.hdr = if dolby_vision {
+        Some("DV")
+    } else {
+        child(children, b"colr")
+            .filter(|colour| colour.starts_with(b"nclx") || colour.starts_with(b"nclc"))
+            .and_then(|colour| colour.get(6..8))
+            .and_then(|transfer| transfer_hdr(uint(transfer)))
+    };
+}
+
+fn read_audio(kind: &[u8; 4], entry: &[u8], info: &mut MediaInfo) {
+    info.audio_codec = match kind {
+        b"mp4a"                               => Some("AAC"),
+        b"ac-3"                               => Some("AC3"),
+        b"ec-3"                               => Some("EAC3"),
+        b"Opus"                               => Some("Opus"),
+        b"fLaC"                               => Some("FLAC"),
+        b"alac"                               => Some("ALAC"),
+        b"mlpa"                               => Some("TrueHD"),
+        b".mp3"                               => Some("MP3"),
+        b"dtsc" | b"dtsh" | b"dtsl" | b"dtse" => Some("DTS"),
+        b"lpcm" | b"sowt" | b"twos"           => Some("PCM"),
+        _                                     => None
+    };
+
+    // The fields of an audio sample entry grow with the version of QuickTime which it was written for.
+    let (channels, fields) = match entry.get(8..10).map(uint) {
+        Some(1) => (entry.get(16..18), 44),
+        Some(2) => (entry.get(40..44), 64),
+        _       => (entry.get(16..18), 28)
+    };
+    info.channels = channels.map(|channels| uint(channels).min(255) as u8);
+
+    // AC-3 and E-AC-3 give their exact layout, as their channel count is often left at two.
+    let children = entry.get(fields..).unwrap_or_default();
+    let mode = child(children, b"dac3").and_then(|data| data.get(1)).map(|&byte| (byte >> 3 & 7, byte >> 2 & 1))
+        .or_else(|| child(children, b"dec3").and_then(|data| data.get(3)).map(|&byte| (byte >> 1 & 7, byte & 1)));
+    if let Some((mode, lfe)) = mode {
+        info.channels = Some(AC3_CHANNELS[mode as usize] + lfe);
+    }
+}
+
+/// The body of the first box of the given kind.
+fn child<'a>(body: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    Boxes(body).find(|entry| &entry.0 == kind).map(|(_, data)| data)
+}
+
+/// The boxes within the body of another box, as their kinds and bodies. Iteration ends at the first box which is
+/// truncated.
+struct Boxes<'a>(&'a [u8]);
+
+impl<'a> Iterator for Boxes<'a> {
+    type Item = ([u8; 4], &'a [u8]);
+
+    fn next(&mut self) -> Option<([u8; 4], &'a [u8])> {
+        let data = self.0;
+        let mut kind = [0; 4];
+        kind.copy_from_slice(data.get(4..8)?);
+        let (start, size) = match uint(data.get(0..4)?) {
+            0 => (8, data.len() as u64),
+            1 => (16, uint(data.get(8..16)?)),
+            size => (8, size)
+        };
+        if size < start as u64 || size > data.len() as u64 { return None }
+        self.0 = &data[size as usize..];
+        Some((kind, &data[start..size as usize]))
+    }
+}
+
+/// Reads the kind and body size of the next box of the input, or nothing at its end. The size is `None` when the box
+/// extends to the end of the input.
+fn header<R: Read>(input: &mut R) -> io::Result<Option<([u8; 4], Option<u64>)>> {
+    let mut bytes = [0u8; 16];
+    match read_up_to(input, &mut bytes[..8])? {
+        0 => return Ok(None),
+        8 => (),
+        _ => return Err(invalid())
+    }
+
+    let mut kind = [0; 4];
+    kind.copy_from_slice(&bytes[4..8]);
+    let size = match uint(&bytes[..4]) {
+        0 => None,
+        1 => {
+            input.read_exact(&mut bytes[8..])?;
+            Some(uint(&bytes[8..]).checked_sub(16).ok_or_else(invalid)?)
+        },
+        size => Some(size.checked_sub(8).ok_or_else(invalid)?)
+    };
+    Ok(Some((kind, size)))
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "not a valid MP4 file")
+}
+
+#[test]
+fn test_inspect() {
+    use std::io::Cursor;
+
+    fn mp4_box(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut bytes = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(kind);
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    // A version 0 movie header of 1,281,000 units of a 500 unit timescale.
+    let mut header = vec![0; 20];
+    header[12..16].copy_from_slice(&500u32.to_be_bytes());
+    header[16..20].copy_from_slice(&1_281_000u32.to_be_bytes());
+
+    let mut visual = vec![0; 78];
+    visual[24..28].copy_from_slice(&[0x05, 0x00, 0x02, 0xD0]);
+    visual.extend(mp4_box(b"colr", b"nclx\x00\x09\x00\x12\x00\x09\x80"));
+    let mut audio = vec![0; 28];
+    audio[16..18].copy_from_slice(&[0, 2]);
+    audio.extend(mp4_box(b"dec3", &[0x00, 0x00, 0x20, 0x0F, 0x00]));
+
+    let track = |handler: &[u8], entry: Vec<u8>| {
+        let description = [&[0, 0, 0, 0, 0, 0, 0, 1][..], &entry].concat();
+        let table = mp4_box(b"stbl", &mp4_box(b"stsd", &description));
+        let media = [mp4_box(b"hdlr", &[&[0; 8][..], handler, &[0; 12]].concat()), mp4_box(b"minf", &table)].concat();
+        mp4_box(b"trak", &mp4_box(b"mdia", &media))
+    };
+    let movie = [mp4_box(b"mvhd", &header), track(b"vide", mp4_box(b"hvc1", &visual)),
+        track(b"soun", mp4_box(b"ec-3", &audio))].concat();
+    let file = [mp4_box(b"ftyp", b"isom\x00\x00\x02\x00"), mp4_box(b"mdat", &[0; 64]), mp4_box(b"moov", &movie)]
+        .concat();
+
+    let media = inspect(&mut Cursor::new(file)).unwrap();
+    assert_eq!((media.width, media.height), (Some(1280), Some(720)));
+    assert_eq!((media.video_codec, media.audio_codec, media.channels), (Some("x265"), Some("EAC3"), Some(6)));
+    assert_eq!(media.hdr, Some("HLG"));
+    assert_eq!(media.duration, Some(Duration::from_secs(2562)));
+}
@@ -1,29 +1,141 @@
-use std::fs::{File, read_dir};
-use std::io::{Error, Read};
-
-/// Obtains a list of video extensions from the `/etc/mime.types` file on Linux.
-pub fn get_extensions(kind: &'static str) -> Result<Vec<String>, Error> {
-    let mut extension_list = Vec::new();
-    let mut buffer = String::new();
-
-    for extension_file in read_dir(&["/usr/share/mime/", kind].concat())? {
-        let extension_file = extension_file?;
-        let mut file = File::open(extension_file.path())?;
-        let capacity = file.metadata().map(|x| x.len()).unwrap_or(0);
-        buffer.reserve(capacity as usize);
-        file.read_to_string(&mut buffer)?;
-
-        for line in buffer.split('\n') {
-            let line = line.trim();
-            if line.starts_with("<glob pattern=\"") {
-                let extension = line.split('"').nth(1)
-                    .and_then(|extension| extension.split('.').last());
-                if let Some(extension) = extension {
-                    extension_list.push(extension.to_owned());
-                }
-            }
+use std::collections::BTreeSet;
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Extensions of video files which are recognized whether or not the shared MIME database is installed.
+const VIDEO_EXTENSIONS: [&'static str; 36] = [
+    "3g2", "3gp", "3gpp", "amv", "asf", "avi", "divx", "dv", "f4v", "flv", "m1v", "m2t", "m2ts", "m2v", "m4v",
+    "mk3d", "mkv", "mov", "mp2", "mp4", "mpe", "mpeg", "mpg", "mts", "mxf", "nsv", "ogm", "ogv", "qt", "rm",
+    "rmvb", "ts", "vob", "webm", "wmv", "wtv",
+];
+
+/// Extensions which the shared MIME database gives to video types, but which are far more often something else, such
+/// as playlists of Blu-ray discs, or audio.
+const NOT_EPISODES: [&'static str; 8] = ["bdm", "bdmv", "clpi", "cpi", "mpl", "mpls", "ogg", "mng"];
+
+/// The extensions of video files: those of the embedded table and of the shared MIME database, along with those
+/// which the configuration includes, and without those which it excludes. Each is lowercase, without a dot.
+pub fn video_extensions(include: &[String], exclude: &[String]) -> BTreeSet<String> {
+    let mut extensions = VIDEO_EXTENSIONS.iter().map(|&extension| extension.to_owned()).collect::<BTreeSet<_>>();
+    extensions.extend(database_extensions());
+
+    let normalize = |extension: &String| extension.trim().trim_start_matches('.').to_lowercase();
+    extensions.extend(include.iter().map(&normalize));
+    for extension in exclude {
+        extensions.remove(&normalize(extension));
+    }
+    extensions
+}
+
+/// The extensions of the video types of the shared MIME database, which is only read the first time that they are
+/// needed, rather than for every season.
+fn database_extensions() -> BTreeSet<String> {
+    static EXTENSIONS: Mutex<Option<BTreeSet<String>>> = Mutex::new(None);
+    let mut extensions = EXTENSIONS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    extensions.get_or_insert_with(|| {
+        globs2_paths().iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .flat_map(|contents| parse_globs2(&contents, "video/"))
+            .filter(|extension| !NOT_EPISODES.contains(&extension.as_str()))
+            .collect()
+    }).clone()
+}
+
+/// Whether a file is a video, by its extension, or by the first bytes of its contents when it has no extension.
+pub fn is_video(path: &Path, extensions: &BTreeSet<String>) -> bool {
+    match path.extension() {
+        Some(extension) => extensions.contains(&extension.to_string_lossy().to_lowercase()),
+        None => sniff(path).is_some()
+    }
+}
+
+/// Identifies the container of a video file by its first bytes, and returns the extension which it is usually given.
+pub fn sniff(path: &Path) -> Option<&'static str> {
+    let mut header = [0u8; 192];
+    let mut file = File::open(path).ok()?;
+    let mut length = 0;
+    while length < header.len() {
+        match file.read(&mut header[length..]) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => length += read
         }
     }
+    container(&header[..length])
+}
+
+/// Identifies a container by its signature.
+fn container(header: &[u8]) -> Option<&'static str> {
+    let at = |offset: usize, signature: &[u8]| header.get(offset..offset + signature.len()) == Some(signature);
+    if at(0, &[0x1A, 0x45, 0xDF, 0xA3]) {
+        // Matroska and WebM share the EBML header, which names the document type.
+        let webm = header.windows(4).any(|window| window == b"webm");
+        Some(if webm { "webm" } else { "mkv" })
+    } else if at(4, b"ftyp") {
+        Some(if at(8, b"qt  ") { "mov" } else { "mp4" })
+    } else if at(0, b"RIFF") && at(8, b"AVI ") {
+        Some("avi")
+    } else if at(0, &[0x30, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11]) {
+        Some("wmv")
+    } else if at(0, b"FLV\x01") {
+        Some("flv")
+    } else if at(0, &[0x00, 0x00, 0x01, 0xBA]) {
+        Some("mpg")
+    } else if at(0, &[0x47]) && at(188, &[0x47]) {
+        // MPEG transport streams have a sync byte at the start of every 188 byte packet.
+        Some("ts")
+    } else {
+        None
+    }
+}
+
+/// The `globs2` files of the shared MIME database, in `$XDG_DATA_HOME` and each of `$XDG_DATA_DIRS`. Relative paths
+/// are ignored, as the XDG Base Directory specification requires.
+fn globs2_paths() -> Vec<PathBuf> {
+    let home = env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::home_dir().map(|home| home.join(".local/share")));
+    let dirs = env::var("XDG_DATA_DIRS").ok().filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| String::from("/usr/local/share:/usr/share"));
+    home.into_iter()
+        .chain(dirs.split(':').map(PathBuf::from).filter(|dir| dir.is_absolute()))
+        .map(|dir| dir.join("mime/globs2"))
+        .collect()
+}
+
+/// Reads the extensions of the MIME types which start with `kind` from a `globs2` file, whose lines take the form
+/// of `weight:type:glob[:flags]`. Globs other than a simple `*.extension` are skipped.
+fn parse_globs2(contents: &str, kind: &str) -> Vec<String> {
+    contents.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let (_weight, mime, glob) = (fields.next()?, fields.next()?, fields.next()?);
+            if !mime.starts_with(kind) { return None }
+            let extension = glob.trim().trim_start_matches("*.");
+            let simple = glob.starts_with("*.") && !extension.is_empty()
+                && extension.chars().all(|character| character.is_alphanumeric());
+            if simple { Some(extension.to_lowercase()) } else { None }
+        })
+        .collect()
+}
+
+#[test]
+fn test_video_extensions() {
+    let globs2 = "# comment\n50:video/x-matroska:*.mkv\n50:video/x-anim:*.anim[1-9j]\n\
+        50:video/x-newformat:*.NEW:cs\n50:audio/flac:*.flac\n";
+    assert_eq!(parse_globs2(globs2, "video/"), vec!["mkv", "new"]);
+
+    let extensions = video_extensions(&[String::from(".STRM")], &[String::from("ts")]);
+    assert!(extensions.contains("mkv") && extensions.contains("strm"));
+    assert!(!extensions.contains("ts") && !extensions.contains("mpls"));
 
-    Ok(extension_list)
+    let mut matroska = vec![0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x82, 0x84];
+    matroska.extend_from_slice(b"webm");
+    assert_eq!(container(&matroska), Some("webm"));
+    assert_eq!(container(b"\x00\x00\x00\x20ftypisom"), Some("mp4"));
+    assert_eq!(container(b"RIFF\x00\x00\x00\x00AVI LIST"), Some("avi"));
+    assert_eq!(container(b"plain text"), None);
 }
@@ -1,14 +1,44 @@
 pub mod traits;
 pub mod tokenizer;
+pub mod sidecar;
+pub mod journal;
+pub mod plan;
+pub mod conflict;
+pub mod action;
+pub mod export;
+pub mod script;
+pub mod provider;
+pub mod config;
+pub mod preflight;
+pub mod report;
+pub mod progress;
+pub mod sort;
+pub mod junk;
+pub mod media;
+pub mod runtime;
+mod date;
+mod exclude;
+mod trash;
 mod mimetypes;
+#[cfg(test)]
+mod testing;
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
 use std::fs::{self, ReadDir};
 use std::io::Error;
 use std::path::{Path, PathBuf};
 
-use tvdb;
+use regex::{self, Regex, RegexBuilder};
 
+use self::action::Action;
+use self::conflict::ConflictPolicy;
+use self::exclude::Rules;
+use self::junk::{Junk, JunkPolicy};
+use self::media::MediaInfo;
+use self::provider::{EpisodeInfo, Ordering};
+use self::script::ScriptFormat;
+use self::sort::SortOrder;
 use self::tokenizer::TemplateToken as Token;
 use self::traits::Digits;
 
@@ -18,21 +48,85 @@ macro_rules! lowercase {
 
 pub const DRY_RUN: u8 = 1;
 pub const VERBOSE: u8 = 2;
+/// Output is written as JSON events, one per line, instead of text.
+pub const JSON: u8 = 4;
+/// Files are renamed even if they do not match the episode guide, and files without an episode keep their names.
+pub const FORCE: u8 = 8;
 
 pub struct Arguments {
-    pub flags:          u8,
-    pub season_index:   u8,
-    pub pad_length:     u8,
-    pub episode_index:  u16,
-    pub base_directory: String,
-    pub series_name:    String,
-    pub template:       Vec<Token>
+    pub flags:            u8,
+    pub on_conflict:      ConflictPolicy,
+    pub action:           Action,
+    /// Which of the provider's orderings the episodes are numbered by.
+    pub ordering:         Ordering,
+    pub season_index:     u16,
+    pub pad_length:       u8,
+    pub episode_index:    u32,
+    pub base_directory:   String,
+    /// Where renamed episodes are placed, if not alongside the originals.
+    pub target_directory: Option<String>,
+    /// Determines which files of the base directory are found.
+    pub scan:             ScanOptions,
+    /// Numbers the episodes of seasons whose file names give no episode numbers by comparing their durations with
+    /// the runtimes of the guide, when the comparison is confident.
+    pub match_runtime:    bool,
+    /// Where the plan is written for review, as JSON or CSV, before any file is renamed.
+    pub plan_out:         Option<String>,
+    /// Prints a script which carries out the plan, instead of renaming anything.
+    pub emit_script:      Option<ScriptFormat>,
+    pub series_name:      String,
+    pub template:         Vec<Token>
+}
+
+impl Arguments {
+    /// Directs the arguments of a library at one of its series, whose episodes are within the given directory of the
+    /// library. When organizing into another directory, each series keeps its directory there, so that the seasons
+    /// of different series are not placed in the same directories.
+    pub fn enter_series(&mut self, directory: &Path) {
+        let relative = directory.strip_prefix(&self.base_directory).ok()
+            .or_else(|| directory.file_name().map(Path::new))
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+        if let Some(ref mut target) = self.target_directory {
+            let joined = Path::new(target.as_str()).join(relative).to_string_lossy().into_owned();
+            *target = joined;
+        }
+        self.base_directory = directory.to_string_lossy().into_owned();
+    }
 }
 
 #[derive(Debug)]
 pub struct Season {
-    pub season_no: u8,
-    pub episodes:  Vec<PathBuf>
+    /// The number of the season, which may be a year, such as 2019.
+    pub season_no: u16,
+    pub episodes:  Vec<Episode>,
+    /// Videos which do not appear to be episodes, such as samples and trailers. They are left out of the episodes
+    /// unless the policy of the scan is to flag them.
+    pub junk:      Vec<Junk>
+}
+
+impl Season {
+    /// Numbers the episodes in the order that they were sorted in, from `episode_start`.
+    pub fn numbers_in_order(&self, episode_start: u32) -> Vec<u32> {
+        (0..self.episodes.len()).map(|index| episode_start.saturating_add(index as u32)).collect()
+    }
+
+    /// Numbers the episodes by the episode numbers that their names give, such as `S01E05`, which leaves gaps where
+    /// episodes are missing. There are no numbers unless every name gives an episode of this season.
+    pub fn numbers_by_name(&self) -> Option<Vec<u32>> {
+        self.episodes.iter()
+            .map(|episode| preflight::episode_hint(&episode.path)
+                .filter(|&(season_no, _)| season_no == self.season_no)
+                .map(|(_, episode_no)| episode_no))
+            .collect()
+    }
+}
+
+/// A video file along with the companion files (subtitles, NFO, artwork) that share its stem.
+#[derive(Debug)]
+pub struct Episode {
+    pub path:     PathBuf,
+    pub sidecars: Vec<PathBuf>
 }
 
 /// Takes a pathname and shortens it for readability.
@@ -47,9 +141,74 @@ pub fn shorten_path(path: &Path) -> PathBuf {
             .map_or_else(|| path.to_path_buf(), |value| PathBuf::from("~").join(value)))
 }
 
+/// Patterns which recognize the name of a season directory, whose first capture group is the number of the season.
+/// Each is matched against the name of the directory alone, ignoring case.
+pub const SEASON_PATTERNS: [&'static str; 5] = [
+    // Season 1, Season 01 (2019), Series 2, Staffel 3, Saison 4, Temporada 5, Stagione 6, Seizoen 7, Säsong 8
+    r"^(?:season|series|staffel|saison|temporada|stagione|seizoen|sezon|säsong|sæson|sesong|kausi)[ ._-]*([0-9]+)\b",
+    // S01, S01 (2019), S2019
+    r"^s([0-9]{1,4})\b",
+    // 第1季, 第 2 期
+    r"^第\s*([0-9]+)\s*[季期部]",
+    // 시즌 1
+    r"^시즌\s*([0-9]+)",
+    // 1. Staffel
+    r"^([0-9]+)\.?\s*staffel\b",
+];
+
+/// Names of directories which hold the specials of a series, numbered as season 0.
+const SPECIALS: [&'static str; 2] = ["specials", "special"];
+
+/// Determines which files are found by a scan.
+#[derive(Clone, Debug)]
+pub struct ScanOptions {
+    /// How many levels of directories below a season directory, or below the series directory when it has no
+    /// season directories, are searched for episodes, such as `Season 1/Disc 2`.
+    pub max_depth:          usize,
+    /// Recognizes season directories by their names, in order. See `SEASON_PATTERNS`.
+    pub season_patterns:    Vec<Regex>,
+    /// The order in which the episodes of a season are numbered.
+    pub sort:               SortOrder,
+    /// Extensions of video files which are not otherwise recognized, such as `strm`.
+    pub include_extensions: Vec<String>,
+    /// Extensions which are not to be taken as video files, although they are recognized, such as `ts`.
+    pub exclude_extensions: Vec<String>,
+    /// What becomes of videos which do not appear to be episodes, such as samples and trailers.
+    pub junk:               JunkPolicy,
+    /// Whether videos which are much shorter than the others of their season are taken as junk, by the durations
+    /// given by their containers.
+    pub junk_durations:     bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> ScanOptions {
+        ScanOptions {
+            max_depth:          2,
+            season_patterns:    SEASON_PATTERNS.iter().filter_map(|pattern| season_pattern(pattern).ok()).collect(),
+            sort:               SortOrder::Natural,
+            include_extensions: Vec::new(),
+            exclude_extensions: Vec::new(),
+            junk:               JunkPolicy::Exclude,
+            junk_durations:     false,
+        }
+    }
+}
+
+/// Compiles a pattern which recognizes season directories, which must capture the number of the season.
+pub fn season_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    let regex = RegexBuilder::new(pattern).case_insensitive(true).build()?;
+    if regex.captures_len() < 2 {
+        return Err(regex::Error::Syntax(String::from("the pattern must capture the number of the season")));
+    }
+    Ok(regex)
+}
+
 pub enum ScanDir {
     Episodes(Season),
-    Seasons(Vec<Season>)
+    Seasons(Vec<Season>),
+    /// A directory without season directories, whose file names give episodes of several seasons, such as the
+    /// complete series in a single directory. Each season is numbered by the episode numbers in its file names.
+    Mixed(Vec<Season>)
 }
 
 quick_error! {
@@ -61,30 +220,85 @@ quick_error! {
         InvalidDirEntry(dir: PathBuf) {
             display("unable to read entry in {:?}.", dir)
         }
-        MimeError(err: Error) {
-            display("error obtaining video extensions from /usr/share/mime/video: {}", err)
+        IgnoreFile(path: PathBuf, err: String) {
+            display("{:?} is not a valid ignore file: {}", path, err)
+        }
+        OrderFile(dir: PathBuf, err: Error) {
+            display("unable to read the order file of {:?}: {}", dir, err)
         }
     }
 }
 
 /// Scans a given directory to determine whether the directory contains seasons or episodes, and returns a result
-/// that matches the situation.
-pub fn scan_directory<P: AsRef<Path>>(directory: P, season_no: u8) -> Result<ScanDir, ReadDirError> {
+/// that matches the situation. Paths listed by `.tvrenamerignore` files, hidden directories, and directories of
+/// bonus material such as `Sample` or `Extras` are left out.
+pub fn scan_directory<P: AsRef<Path>>(directory: P, season_no: u16, options: &ScanOptions)
+    -> Result<ScanDir, ReadDirError>
+{
     let directory: &Path = directory.as_ref();
+    let rules = Rules::default().enter(directory)?;
     // Attempt to read a list of files in a given directory
     for entry in fs::read_dir(directory).map_err(|_| ReadDirError::UnableToReadDir(directory.to_path_buf()))? {
         // Check if the current entry is valid and return an error if not.
         let entry = entry.map(|entry| entry.path())
             .map_err(|_| ReadDirError::InvalidDirEntry(directory.to_path_buf()))?;
 
-        // If the entry is a directory and the directory contains `season`, return a list of seasons
-        if entry.to_str().map(|s| s.to_lowercase().contains("season") && entry.is_dir()).unwrap_or(false) {
-            return get_seasons(directory).map(ScanDir::Seasons);
+        // If the entry is a directory whose name is that of a season, return a list of seasons.
+        let is_season = entry.file_name().and_then(|name| name.to_str())
+            .map_or(false, |name| options.season_patterns.iter().any(|pattern| pattern.is_match(name.trim())));
+        if is_season && entry.is_dir() && !rules.is_excluded(&entry, true) {
+            return get_seasons(directory, options, &rules).map(ScanDir::Seasons);
         }
     }
 
     // If the directory does not contain season directories, return a list of episodes.
-    get_episodes(directory, season_no).map(ScanDir::Episodes)
+    get_episodes(directory, season_no, options, &Rules::default()).map(group_seasons)
+}
+
+/// Groups the episodes of a directory without season directories by the season given in their file names, such as
+/// `S02E05`, when they give more than one. Files whose names give no season remain in the season they were given.
+fn group_seasons(season: Season) -> ScanDir {
+    let hinted = season.episodes.iter()
+        .filter_map(|episode| preflight::episode_hint(&episode.path).map(|(season_no, _)| season_no))
+        .collect::<BTreeSet<u16>>();
+    if hinted.len() < 2 { return ScanDir::Episodes(season) }
+
+    let default = season.season_no;
+    let season_of = |path: &Path| preflight::episode_hint(path).map_or(default, |(season_no, _)| season_no);
+    let mut seasons = BTreeMap::new();
+    for episode in season.episodes {
+        seasons.entry(season_of(&episode.path)).or_insert_with(Vec::new).push(episode);
+    }
+
+    let mut seasons = seasons.into_iter()
+        .map(|(season_no, episodes)| Season { season_no: season_no, episodes: episodes, junk: Vec::new() })
+        .collect::<Vec<Season>>();
+
+    // Junk is listed with the season its name gives, or else with the first season.
+    for junk in season.junk {
+        let season_no = season_of(&junk.path);
+        let index = seasons.iter().position(|season| season.season_no == season_no).unwrap_or(0);
+        seasons[index].junk.push(junk);
+    }
+
+    ScanDir::Mixed(seasons)
+}
+
+/// Lists the directories of a library, each of which holds a single series and is named after it. Hidden directories
+/// are left out, and the rest are sorted by name.
+pub fn scan_library<P: AsRef<Path>>(library: P) -> Result<Vec<PathBuf>, ReadDirError> {
+    let library: &Path = library.as_ref();
+    let mut series = Vec::new();
+    for entry in read_directory(library)? {
+        let path = entry.map_err(|_| ReadDirError::InvalidDirEntry(library.to_path_buf()))?.path();
+        let hidden = path.file_name().map_or(true, |name| name.to_string_lossy().starts_with('.'));
+        if path.is_dir() && !hidden {
+            series.push(path);
+        }
+    }
+
+    series.sort_by(|a, b| lowercase!(a).cmp(&lowercase!(b)));
+    Ok(series)
 }
 
 pub enum TargetErr {
@@ -93,15 +307,17 @@ pub enum TargetErr {
     Parent
 }
 
-/// Target requires source path, template tokens, episode number, and name of TV series
-pub fn collect_target(source: &Path, season_no: u8, episode_no: u16, arguments: &Arguments,
-    tvdb_api: &tvdb::Tvdb, tvdb_series_id: u32)-> Result<PathBuf, TargetErr>
+/// Target requires source path, template tokens, episode number, name of TV series, and its episode guide
+pub fn collect_target(source: &Path, season_no: u16, episode_no: u32, arguments: &Arguments,
+    guide: &[EpisodeInfo]) -> Result<PathBuf, TargetErr>
 {
-    let ep = tvdb::data::EpisodeId::new(tvdb_series_id, "zh-CN");
-    let episode = tvdb_api.episode(ep)
-        .map_err(|_| TargetErr::EpisodeDoesNotExist)?;
-
-    let epdata = &episode.data.unwrap();
+    let epdata = provider::find(guide, season_no, episode_no).ok_or(TargetErr::EpisodeDoesNotExist)?;
+    // The container is only read when the template names its properties, which are left out when it cannot be read.
+    let media = if arguments.template.iter().any(Token::is_media) {
+        media::inspect(source).unwrap_or_default()
+    } else {
+        MediaInfo::default()
+    };
     let mut filename = String::with_capacity(64);
     for pattern in &arguments.template {
         match *pattern {
@@ -109,44 +325,58 @@ pub fn collect_target(source: &Path, season_no: u8, episode_no: u16, arguments:
             Token::Series           => filename.push_str(&arguments.series_name),
             Token::Season           => filename.push_str(&season_no.to_string()),
             Token::Episode          => filename.push_str(&episode_no.to_padded_string('0', arguments.pad_length as usize)),
-            Token::TvdbTitle        => filename.push_str(epdata.episode_name.as_str()),
-            Token::TvdbFirstAired   => if let Some(date) = epdata.first_aired.clone() {
+            Token::TvdbTitle        => filename.push_str(epdata.title.as_str()),
+            Token::TvdbFirstAired   => if let Some(ref date) = epdata.first_aired {
                 filename.push_str(date.as_str());
                 // filename.push_str(&date.year.to_string());
                 // filename.push('-');
                 // filename.push_str(&date.month.to_padded_string('0', 2));
                 // filename.push('-');
                 // filename.push_str(&date.day.to_padded_string('0', 2));
-            }
+            },
+            Token::Resolution       => filename.push_str(&media.resolution().unwrap_or_default()),
+            Token::VideoCodec       => filename.push_str(media.video_codec.unwrap_or_default()),
+            Token::AudioCodec       => filename.push_str(media.audio_codec.unwrap_or_default()),
+            Token::Channels         => filename.push_str(&media.channel_layout().unwrap_or_default()),
+            Token::Hdr              => filename.push_str(media.hdr.unwrap_or_default()),
+            Token::Duration         => filename.push_str(&media.minutes().unwrap_or_default()),
         }
     }
 
+    // Videos without an extension are given the one of their container.
     let extension = source.extension()
         .and_then(|s| s.to_str())
+        .or_else(|| mimetypes::sniff(source))
         .ok_or(TargetErr::Extension)?;
 
     filename = [&filename.trim().replace("/", "-"), ".", &extension].concat();
 
-    source.parent()
-        .map(|parent| PathBuf::from(parent).join(filename))
-        .ok_or(TargetErr::Parent)
+    let parent = source.parent().ok_or(TargetErr::Parent)?;
+
+    // When organizing into another directory, episodes keep their place relative to the base directory.
+    let parent = match arguments.target_directory {
+        Some(ref directory) => Path::new(directory)
+            .join(parent.strip_prefix(&arguments.base_directory).unwrap_or(Path::new(""))),
+        None => parent.to_path_buf()
+    };
+
+    Ok(parent.join(filename))
 }
 
 fn read_directory(directory: &Path) -> Result<ReadDir, ReadDirError> {
     fs::read_dir(directory).map_err(|_| ReadDirError::UnableToReadDir(directory.to_owned()))
 }
 
-/// Collects a list of all episodes belonging to each season within a given directory.
-fn get_seasons<P: AsRef<Path>>(directory: P) -> Result<Vec<Season>, ReadDirError> {
-    let directory: &Path = directory.as_ref();
+/// Collects a list of all episodes belonging to each season within a given directory, whose ignore rules apply.
+fn get_seasons(directory: &Path, options: &ScanOptions, rules: &Rules) -> Result<Vec<Season>, ReadDirError> {
     let mut output: Vec<Season> = Vec::new();
 
     for entry in read_directory(directory)? {
         let entry = entry.map_err(|_| ReadDirError::InvalidDirEntry(directory.to_path_buf()))?;
         let season = entry.path();
-        if season.is_dir() {
-            if let Some(number) = derive_season_number(&season) {
-                output.push(get_episodes(&season, number)?);
+        if season.is_dir() && !rules.is_excluded(&season, true) {
+            if let Some(number) = derive_season_number(&season, &options.season_patterns) {
+                output.push(get_episodes(&season, number, options, rules)?);
             }
         }
     }
@@ -156,79 +386,193 @@ fn get_seasons<P: AsRef<Path>>(directory: P) -> Result<Vec<Season>, ReadDirError
     Ok(output)
 }
 
-/// Collects a list of all of the episodes in a given directory. Files that are not videos are ignored.
-fn get_episodes<P: AsRef<Path>>(directory: P, season_no: u8) -> Result<Season, ReadDirError> {
-    let directory: &Path = directory.as_ref();
-    // Collect a list of video extensions
-    let video_extensions = mimetypes::get_extensions("video").map_err(ReadDirError::MimeError)?;
+/// Collects a list of all of the episodes in a given directory and its subdirectories, along with their companion
+/// files, under the ignore rules of the directories above it. Files that are neither videos nor companions of a
+/// video are ignored.
+fn get_episodes(directory: &Path, season_no: u16, options: &ScanOptions, rules: &Rules)
+    -> Result<Season, ReadDirError>
+{
+    let mut files = Vec::new();
+    collect_files(directory, options.max_depth, rules, &mut files)?;
 
-    // It is more likely that all files will have the same extension, so it
-    // will be useful to check for the last-matched extension with each
-    // file once the first match has been completed.
-    let mut last_matched_extension: Option<String> = None;
+    // Videos are recognized by their extensions, or by their contents when they have none.
+    let video_extensions = mimetypes::video_extensions(&options.include_extensions, &options.exclude_extensions);
 
-    // Collect a list of episodes in the directory
+    // Collect a list of episodes in the directory, and a list of files that may accompany them.
     let mut episodes = Vec::with_capacity(32);
-    for entry in read_directory(directory)? {
-        let entry = entry.map_err(|_| ReadDirError::InvalidDirEntry(directory.to_path_buf()))?;
-        let path = entry.path();
-        let mut pushed = false;
-        if path.is_file() {
-            // Only collect videos from a list of known supported video extensions.
-            if let Some(ref extension) = last_matched_extension {
-                // Only collect files that contain extensions
-                path.extension().map(|entry| {
-                    // If the video extension matches the current file, append it to the list of episodes.
-                    if Some(extension.as_str()) == entry.to_str() {
-                        episodes.push(path.clone());
-                        pushed = true;
-                    }
-                });
-
-                if pushed { continue }
-            }
-
-            for extension in &video_extensions {
-                // Only collect files that contain extensions
-                path.extension().map(|entry| {
-                    // If the video extension matches the current file, append it to the list of episodes.
-                    if Some(extension.as_str()) == entry.to_str() {
-                        episodes.push(path.clone());
-                        last_matched_extension = Some(extension.clone());
-                        pushed = true;
-                    }
-                });
-
-                if pushed { break }
-            }
+    let mut companions = Vec::new();
+    for path in files {
+        if mimetypes::is_video(&path, &video_extensions) {
+            episodes.push(path);
+        } else if sidecar::is_sidecar(&path) {
+            companions.push(path);
         }
     }
 
-    episodes.sort_by(|a, b| lowercase!(a).cmp(&lowercase!(b)));
+    sort::sort(&mut episodes, directory, options.sort)
+        .map_err(|why| ReadDirError::OrderFile(directory.to_path_buf(), why))?;
+
+    // Samples, trailers and bonus material would shift the number of every episode after them.
+    let junk = junk::detect(&episodes, options.junk_durations);
+    if options.junk == JunkPolicy::Exclude {
+        episodes.retain(|path| !junk.iter().any(|junk| &junk.path == path));
+    }
+
+    // Pair each episode with the subtitles, metadata and artwork that share its stem.
+    let sidecars = sidecar::associate(&episodes, companions);
+    let episodes = episodes.into_iter().zip(sidecars)
+        .map(|(path, sidecars)| Episode { path: path, sidecars: sidecars })
+        .collect();
 
     // Return the list of episodes as a `Season` with the accompanying season number.
-    Ok(Season { season_no: season_no, episodes: episodes })
-}
-
-/// Given a directory path, derive the number of the season and assign it.
-pub fn derive_season_number(season: &Path) -> Option<u8> {
-    season.file_name().and_then(|season| season.to_str())
-        .map(|season| season.to_lowercase())
-        .and_then(|mut directory_name| match directory_name.as_str() {
-            "season0" | "season 0" | "specials" => Some(0),
-            _ => {
-                directory_name = directory_name.replace("season", "");
-                directory_name = directory_name.replace(" ", "");
-                directory_name.parse::<u8>().ok()
+    Ok(Season { season_no: season_no, episodes: episodes, junk: junk })
+}
+
+/// Collects the files within a directory, and within its subdirectories down to the given depth, which are not
+/// excluded by the ignore rules.
+fn collect_files(directory: &Path, depth: usize, rules: &Rules, files: &mut Vec<PathBuf>) -> Result<(), ReadDirError> {
+    let rules = rules.enter(directory)?;
+    for entry in read_directory(directory)? {
+        let path = entry.map_err(|_| ReadDirError::InvalidDirEntry(directory.to_path_buf()))?.path();
+        if path.is_dir() {
+            if depth > 0 && !rules.is_excluded(&path, true) {
+                collect_files(&path, depth - 1, &rules, files)?;
             }
-        })
+        } else if path.is_file() && !rules.is_excluded(&path, false) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Given a directory path, derive the number of the season from the name of the directory, with the first of the
+/// patterns that matches it.
+pub fn derive_season_number(season: &Path, patterns: &[Regex]) -> Option<u16> {
+    let name = season.file_name().and_then(|season| season.to_str())?.trim();
+    if SPECIALS.contains(&name.to_lowercase().as_str()) { return Some(0) }
+    patterns.iter()
+        .filter_map(|pattern| pattern.captures(name))
+        .filter_map(|captures| captures.get(1))
+        .filter_map(|number| number.as_str().parse::<u16>().ok())
+        .next()
 }
 
 #[test]
 fn test_derive_season_number() {
-    assert_eq!(derive_season_number(&Path::new("Specials")), Some(0));
-    assert_eq!(derive_season_number(&Path::new("Season 0")), Some(0));
-    assert_eq!(derive_season_number(&Path::new("Season 1")), Some(1));
-    assert_eq!(derive_season_number(&Path::new("season9")), Some(9));
-    assert_eq!(derive_season_number(&Path::new("Extras")), None);
+    let patterns = ScanOptions::default().season_patterns;
+    let season = |name: &str| derive_season_number(Path::new(name), &patterns);
+    assert_eq!(season("Specials"), Some(0));
+    assert_eq!(season("Season 0"), Some(0));
+    assert_eq!(season("Season 1"), Some(1));
+    assert_eq!(season("season9"), Some(9));
+    assert_eq!(season("Season 01 (2019)"), Some(1));
+    assert_eq!(season("S02"), Some(2));
+    assert_eq!(season("Season 2019"), Some(2019));
+    assert_eq!(season("S2020"), Some(2020));
+    assert_eq!(season("Series 2"), Some(2));
+    assert_eq!(season("Staffel 3"), Some(3));
+    assert_eq!(season("Saison 4"), Some(4));
+    assert_eq!(season("第1季"), Some(1));
+    assert_eq!(season("Extras"), None);
+    assert_eq!(season("Seasonal Shorts"), None);
+    assert_eq!(season("Sherlock"), None);
+    assert_eq!(derive_season_number(Path::new("Season/Disc 1"), &patterns), None);
+}
+
+#[test]
+fn test_scan_directory() {
+    use self::testing::TempDir;
+
+    let directory = TempDir::new("scan");
+    for path in &["Season 1/Disc 1/a.mkv", "Season 1/Disc 2/b.mkv", "Season 1/Sample/sample.mkv",
+        "Season 1/Disc 2/Deleted/c.mkv", "Season 2/d.mkv", "Extras/e.mkv"]
+    {
+        let path = directory.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"").unwrap();
+    }
+    fs::write(directory.join("Season 1/.tvrenamerignore"), "Deleted/\n").unwrap();
+
+    let names = |season: &Season| season.episodes.iter()
+        .map(|episode| episode.path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    match scan_directory(&directory, 1, &ScanOptions::default()).unwrap() {
+        ScanDir::Seasons(seasons) => {
+            assert_eq!(names(&seasons[0]), vec!["a.mkv", "b.mkv"]);
+            assert_eq!(names(&seasons[1]), vec!["d.mkv"]);
+        },
+        _ => panic!("expected season directories")
+    }
+
+    match scan_directory(&directory, 1, &ScanOptions { max_depth: 0, ..ScanOptions::default() }).unwrap() {
+        ScanDir::Seasons(seasons) => assert!(seasons[0].episodes.is_empty()),
+        _ => panic!("expected season directories")
+    }
+}
+
+#[test]
+fn test_group_seasons() {
+    let season = Season { season_no: 1, episodes: ["Show S02E01.mkv", "Show S01E01.mkv", "Show S01E02.mkv", "Bonus.mkv"]
+        .iter().map(|name| Episode { path: PathBuf::from(name), sidecars: Vec::new() }).collect(), junk: Vec::new() };
+
+    match group_seasons(season) {
+        ScanDir::Mixed(seasons) => {
+            assert_eq!(seasons.iter().map(|season| (season.season_no, season.episodes.len())).collect::<Vec<_>>(),
+                vec![(1, 3), (2, 1)]);
+            // The bonus file gives no episode number, so the first season cannot be numbered by its names.
+            assert_eq!(seasons[0].numbers_by_name(), None);
+            assert_eq!(seasons[0].numbers_in_order(1), vec![1, 2, 3]);
+        },
+        _ => panic!("expected several seasons")
+    }
+
+    // The third episode of the first season follows the first, as the second is missing.
+    let season = Season { season_no: 1, episodes: ["Show S01E01.mkv", "Show S01E03.mkv", "Show S02E01.mkv"]
+        .iter().map(|name| Episode { path: PathBuf::from(name), sidecars: Vec::new() }).collect(), junk: Vec::new() };
+    match group_seasons(season) {
+        ScanDir::Mixed(seasons) => {
+            assert_eq!(seasons.iter().map(Season::numbers_by_name).collect::<Vec<_>>(),
+                vec![Some(vec![1, 3]), Some(vec![1])]);
+        },
+        _ => panic!("expected several seasons")
+    }
+
+    let episodes = vec![Episode { path: PathBuf::from("S01E01.mkv"), sidecars: Vec::new() }];
+    let season = Season { season_no: 3, episodes: episodes, junk: Vec::new() };
+    match group_seasons(season) {
+        ScanDir::Episodes(season) => assert_eq!(season.season_no, 3),
+        _ => panic!("expected a single season")
+    }
+}
+
+#[test]
+fn test_enter_series() {
+    use self::testing::TempDir;
+
+    let library = TempDir::new("library");
+    for path in &["Firefly/Season 1/a.mkv", "The Wire/Season 1/b.mkv"] {
+        fs::create_dir_all(library.join(path).parent().unwrap()).unwrap();
+        fs::write(library.join(path), b"").unwrap();
+    }
+    let guide = vec![EpisodeInfo {
+        season_no: 1, episode_no: 1, absolute_no: None, title: String::from("Pilot"), first_aired: None, runtime: None
+    }];
+
+    // Each series of a library is placed in its own directory of the target.
+    let targets = scan_library(&library).unwrap().iter().map(|directory| {
+        let mut arguments = Arguments {
+            flags: 0, on_conflict: ConflictPolicy::Abort, action: Action::Move, ordering: Ordering::Aired,
+            season_index: 1, pad_length: 2, episode_index: 1, base_directory: library.to_string_lossy().into_owned(),
+            target_directory: Some(String::from("/media/tv")), scan: ScanOptions::default(), match_runtime: false,
+            plan_out: None, emit_script: None, series_name: String::new(), template: vec![Token::Episode]
+        };
+        arguments.enter_series(directory);
+        let season = match scan_directory(&arguments.base_directory, 1, &arguments.scan).ok() {
+            Some(ScanDir::Seasons(mut seasons)) => seasons.remove(0),
+            _ => panic!("expected season directories")
+        };
+        collect_target(&season.episodes[0].path, 1, 1, &arguments, &guide).ok().unwrap()
+    }).collect::<Vec<_>>();
+    assert_eq!(targets, vec![PathBuf::from("/media/tv/Firefly/Season 1/01.mkv"),
+        PathBuf::from("/media/tv/The Wire/Season 1/01.mkv")]);
 }
@@ -0,0 +1,556 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use super::action::{self, Action};
+use super::conflict::ConflictPolicy;
+use super::trash::{self, Trashed};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum PlanError {
+        SourceMissing(source: PathBuf) {
+            display("{:?} no longer exists", source)
+        }
+        DuplicateSource(source: PathBuf) {
+            display("{:?} would be renamed more than once", source)
+        }
+        DuplicateTarget(target: PathBuf, first: PathBuf, second: PathBuf) {
+            display("both {:?} and {:?} would be renamed to {:?}", first, second, target)
+        }
+        TargetExists(target: PathBuf) {
+            display("{:?} already exists", target)
+        }
+        InsufficientSpace(directory: PathBuf, required: u64, available: u64) {
+            display("{} bytes are needed in {:?}, but only {} bytes are available", required, directory, available)
+        }
+        SpaceUnknown(directory: PathBuf, err: io::Error) {
+            display("unable to determine the free space in {:?}: {}", directory, err)
+        }
+        Transaction(err: TransactionError) {
+            from()
+            display("{}", err)
+        }
+    }
+}
+
+/// A single file to be renamed, along with the episode that its new name was derived from.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Operation {
+    pub source:     PathBuf,
+    pub target:     PathBuf,
+    pub series_id:  u32,
+    pub season_no:  u16,
+    pub episode_no: u32,
+}
+
+/// The files which an executed plan moved out of the way of its renames.
+#[derive(Debug, Default)]
+pub struct Outcome {
+    /// Files which were moved into the Trash, which may be restored from it.
+    pub trashed:     Vec<Trashed>,
+    /// Files which were replaced, and no longer exist.
+    pub overwritten: Vec<PathBuf>,
+}
+
+/// Every rename that a run intends to perform, which is executed as a single transaction.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RenamePlan {
+    /// Name of the series that the new names were derived from.
+    #[serde(default)]
+    pub series_name: String,
+    /// How each file is placed at its new name.
+    #[serde(default)]
+    pub action:      Action,
+    pub operations:  Vec<Operation>,
+    /// Operations whose existing target is moved into the Trash before any file is renamed.
+    #[serde(default)]
+    pub displaced:   Vec<Operation>,
+}
+
+impl RenamePlan {
+    pub fn new(action: Action) -> RenamePlan { RenamePlan { action: action, ..RenamePlan::default() } }
+
+    pub fn push(&mut self, operation: Operation) { self.operations.push(operation); }
+
+    pub fn is_empty(&self) -> bool { self.operations.is_empty() }
+
+    /// Ensures that every file to be renamed still exists, as a plan may have been written long before it is
+    /// applied. Files that were to be moved into the Trash, but have since disappeared, no longer need to be.
+    pub fn validate(&mut self) -> Result<(), PlanError> {
+        if let Some(operation) = self.operations.iter().find(|operation| !operation.source.exists()) {
+            return Err(PlanError::SourceMissing(operation.source.clone()));
+        }
+        self.displaced.retain(|operation| operation.target.exists());
+        Ok(())
+    }
+
+    /// Operations whose target already exists, and is not moved out of the way by another operation in the plan or
+    /// into the Trash.
+    pub fn occupied(&self) -> Vec<&Operation> {
+        let sources = if self.action.vacates_source() {
+            self.operations.iter().map(|operation| &operation.source).collect::<HashSet<_>>()
+        } else {
+            HashSet::new()
+        };
+
+        self.operations.iter()
+            .filter(|operation| operation.source != operation.target)
+            .filter(|operation| !sources.contains(&operation.target) && operation.target.exists())
+            .filter(|operation| !self.displaced.iter().any(|displaced| displaced.target == operation.target))
+            .collect()
+    }
+
+    /// Applies the conflict policy to every operation whose target is occupied. When the policy is
+    /// `ConflictPolicy::Ask`, `ask` is called to choose the policy for each conflict in turn. The operations which
+    /// were skipped are returned.
+    pub fn resolve_conflicts<F>(&mut self, policy: ConflictPolicy, mut ask: F) -> Result<Vec<Operation>, PlanError>
+        where F: FnMut(&Operation) -> ConflictPolicy
+    {
+        let mut skipped = Vec::new();
+        let occupied = self.occupied().into_iter().map(|operation| operation.target.clone()).collect::<Vec<_>>();
+        for target in occupied {
+            // The operation may have already been skipped or suffixed along with another file of its episode.
+            let id = match self.operations.iter().position(|operation| operation.target == target) {
+                Some(id) => id,
+                None => continue
+            };
+
+            let policy = match policy {
+                ConflictPolicy::Ask => ask(&self.operations[id]),
+                policy => policy
+            };
+
+            match policy {
+                ConflictPolicy::Overwrite => (),
+                ConflictPolicy::Skip => {
+                    let episode = self.episode(id);
+                    let mut removed = episode.iter().rev().map(|&id| self.operations.remove(id)).collect::<Vec<_>>();
+                    removed.reverse();
+                    skipped.extend(removed);
+                },
+                ConflictPolicy::Suffix => self.suffix(id),
+                ConflictPolicy::Trash => self.displaced.push(self.operations[id].clone()),
+                ConflictPolicy::Ask | ConflictPolicy::Abort => return Err(PlanError::TargetExists(target))
+            }
+        }
+        Ok(skipped)
+    }
+
+    /// Indices of the operations which rename the same episode as the given operation: the video, which is planned
+    /// first, followed by its companion files.
+    fn episode(&self, id: usize) -> Vec<usize> {
+        let operation = &self.operations[id];
+        self.operations.iter().enumerate()
+            .filter(|&(_, other)| other.series_id == operation.series_id && other.season_no == operation.season_no
+                && other.episode_no == operation.episode_no && other.source.parent() == operation.source.parent())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Adds the lowest numbered suffix, starting from ` (2)`, to the new names of every file in the given operation's
+    /// episode, such that none of the new names are occupied.
+    fn suffix(&mut self, id: usize) {
+        let episode = self.episode(id);
+        let stem = self.operations[episode[0]].target.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut number = 2;
+        loop {
+            let targets = episode.iter()
+                .map(|&id| suffixed(&self.operations[id].target, &stem, number))
+                .collect::<Vec<_>>();
+
+            let free = targets.iter().all(|target| {
+                !target.exists() && !self.operations.iter().any(|operation| &operation.target == target)
+            });
+
+            if free {
+                for (&id, target) in episode.iter().zip(targets) {
+                    self.operations[id].target = target;
+                }
+                return
+            }
+
+            number += 1;
+        }
+    }
+
+    /// Validates the complete plan and determines the order in which its operations must be carried out so that no
+    /// rename clobbers a file that has yet to be renamed.
+    ///
+    /// - Operations which would rename a file to its own name are dropped.
+    /// - Two sources being renamed to the same target, or one source being renamed twice, is an error.
+    /// - Chains such as `A -> B, B -> C` are ordered so that `B -> C` happens first.
+    /// - Cycles such as `A -> B, B -> A` are broken by moving one file to a temporary name in its directory.
+    ///
+    /// Chains and cycles only arise when the action moves files; copies and links leave every source in place.
+    pub fn schedule(&self) -> Result<Vec<Operation>, PlanError> {
+        let operations = self.operations.iter()
+            .filter(|operation| operation.source != operation.target)
+            .collect::<Vec<_>>();
+
+        let mut sources = HashMap::with_capacity(operations.len());
+        let mut targets = HashMap::with_capacity(operations.len());
+        for (id, operation) in operations.iter().enumerate() {
+            if sources.insert(&operation.source, id).is_some() {
+                return Err(PlanError::DuplicateSource(operation.source.clone()));
+            }
+            if let Some(other) = targets.insert(&operation.target, id) {
+                return Err(PlanError::DuplicateTarget(operation.target.clone(),
+                    operations[other].source.clone(), operation.source.clone()));
+            }
+        }
+
+        if !self.action.vacates_source() {
+            return Ok(operations.into_iter().cloned().collect());
+        }
+
+        // An operation is blocked by the operation that moves its target out of the way, which must happen first.
+        // As sources and targets are unique, each operation blocks at most one other.
+        let blockers = operations.iter()
+            .map(|operation| sources.get(&operation.target).cloned())
+            .collect::<Vec<Option<usize>>>();
+        let mut dependents: Vec<Option<usize>> = vec![None; operations.len()];
+        for (id, blocker) in blockers.iter().enumerate() {
+            if let Some(blocker) = *blocker { dependents[blocker] = Some(id); }
+        }
+
+        let mut steps = Vec::with_capacity(operations.len());
+        let mut scheduled = vec![false; operations.len()];
+
+        // Chains begin with the operation whose target is free, and each rename vacates the target of the next.
+        for start in (0..operations.len()).filter(|&id| blockers[id].is_none()) {
+            let mut next = Some(start);
+            while let Some(id) = next {
+                steps.push(operations[id].clone());
+                scheduled[id] = true;
+                next = dependents[id];
+            }
+        }
+
+        // Every operation that remains is part of a cycle, which is broken by first moving one of its files to a
+        // temporary name, and moving it to its target once the rest of the cycle has been renamed.
+        let mut temporaries = 0;
+        for start in 0..operations.len() {
+            if scheduled[start] { continue }
+            let temporary = temporary_path(&operations[start].source, "tmp", &mut temporaries);
+
+            let mut departure = operations[start].clone();
+            departure.target = temporary.clone();
+            steps.push(departure);
+            scheduled[start] = true;
+
+            let mut next = dependents[start];
+            while let Some(id) = next {
+                if id == start { break }
+                steps.push(operations[id].clone());
+                scheduled[id] = true;
+                next = dependents[id];
+            }
+
+            let mut arrival = operations[start].clone();
+            arrival.source = temporary;
+            steps.push(arrival);
+        }
+
+        Ok(steps)
+    }
+
+    /// Ensures that every file system receiving copies has enough free space for all of them.
+    fn check_space(&self, steps: &[Operation]) -> Result<(), PlanError> {
+        let mut required: HashMap<u64, (PathBuf, u64)> = HashMap::new();
+        for step in steps {
+            let directory = match step.target.parent() {
+                Some(directory) => directory,
+                None => continue
+            };
+
+            let device = match action::device(directory) {
+                Some(device) => device,
+                None => continue
+            };
+
+            let consumes_space = match self.action {
+                Action::Copy => true,
+                Action::Move => action::device(&step.source) != Some(device),
+                _ => false
+            };
+
+            if consumes_space {
+                let size = fs::metadata(&step.source).map(|metadata| metadata.len()).unwrap_or(0);
+                required.entry(device).or_insert_with(|| (directory.to_path_buf(), 0)).1 += size;
+            }
+        }
+
+        for (_, (directory, required)) in required {
+            let available = action::available_space(&directory)
+                .map_err(|why| PlanError::SpaceUnknown(directory.clone(), why))?;
+            if required > available {
+                return Err(PlanError::InsufficientSpace(directory, required, available));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves any displaced files into the Trash, sets aside the files which are to be overwritten, and then applies
+    /// every operation in the order given by `schedule`. If any operation fails, the operations which were already
+    /// applied are reverted in reverse order and the displaced and overwritten files are restored, so that the
+    /// directory is left as it was found. Overwritten files are only removed once every operation has succeeded.
+    pub fn execute(&self) -> Result<Outcome, PlanError> {
+        let steps = self.schedule()?;
+        self.check_space(&steps)?;
+        let overwritten = self.occupied();
+
+        let mut trashed = Vec::with_capacity(self.displaced.len());
+        for operation in &self.displaced {
+            match trash::trash(&operation.target) {
+                Ok(file) => trashed.push(file),
+                Err(why) => {
+                    let failed = Operation {
+                        source: operation.target.clone(),
+                        target: trash::home_trash().unwrap_or_default(),
+                        ..operation.clone()
+                    };
+                    return Err(PlanError::Transaction(rollback(self.action, &[], &[], &trashed, failed, why)));
+                }
+            }
+        }
+
+        // Only renames replace an existing target, so the targets which the conflict policy chose to overwrite are
+        // moved to a temporary name in their directory, where they can be restored from.
+        let mut parked = Vec::with_capacity(overwritten.len());
+        let mut temporaries = 0;
+        for operation in overwritten {
+            let temporary = temporary_path(&operation.target, "replaced", &mut temporaries);
+            if let Err(why) = fs::rename(&operation.target, &temporary) {
+                let failed = Operation { source: operation.target.clone(), target: temporary, ..operation.clone() };
+                return Err(PlanError::Transaction(rollback(self.action, &[], &parked, &trashed, failed, why)));
+            }
+            parked.push((operation.target.clone(), temporary));
+        }
+
+        for (id, step) in steps.iter().enumerate() {
+            if let Err(why) = self.action.apply(&step.source, &step.target) {
+                let failed = step.clone();
+                return Err(PlanError::Transaction(rollback(self.action, &steps[..id], &parked, &trashed, failed, why)));
+            }
+        }
+
+        // The transaction can no longer be rolled back, so a file which could not be removed is merely left behind.
+        for &(_, ref temporary) in &parked {
+            let _ = fs::remove_file(temporary);
+        }
+        Ok(Outcome { trashed: trashed, overwritten: parked.into_iter().map(|(original, _)| original).collect() })
+    }
+}
+
+/// Inserts a numbered suffix after the given stem, or before the extension if the file name does not begin with it.
+fn suffixed(path: &Path, stem: &str, number: usize) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let split = if !stem.is_empty() && name.starts_with(stem) {
+        stem.len()
+    } else {
+        name.rfind('.').unwrap_or(name.len())
+    };
+    path.with_file_name(format!("{} ({}){}", &name[..split], number, &name[split..]))
+}
+
+/// Obtains an unused name in the same directory as `path`, which a file may be parked at while breaking a cycle, or
+/// while it is being overwritten. Names of each purpose are given their own extension, as those which are used to
+/// break cycles are chosen before any file is parked at them.
+fn temporary_path(path: &Path, extension: &str, counter: &mut usize) -> PathBuf {
+    let directory = path.parent().unwrap_or(Path::new("."));
+    loop {
+        *counter += 1;
+        let candidate = directory.join(format!(".tv-renamer-{}-{}.{}", process::id(), counter, extension));
+        if !candidate.exists() { return candidate }
+    }
+}
+
+/// Reverts the given applied operations in reverse order, restores any overwritten and trashed files, and reports on
+/// the failed operation and the outcome. Overwritten files are given as their original and temporary paths.
+fn rollback(action: Action, applied: &[Operation], parked: &[(PathBuf, PathBuf)], trashed: &[Trashed],
+    failed: Operation, cause: io::Error) -> TransactionError
+{
+    let mut unrestored = Vec::new();
+    for operation in applied.iter().rev() {
+        if let Err(why) = action.revert(&operation.source, &operation.target) {
+            unrestored.push((operation.clone(), why));
+        }
+    }
+
+    for &(ref original, ref temporary) in parked.iter().rev() {
+        if let Err(why) = fs::rename(temporary, original) {
+            let operation = Operation { source: original.clone(), target: temporary.clone(), ..failed.clone() };
+            unrestored.push((operation, why));
+        }
+    }
+
+    for file in trashed.iter().rev() {
+        if let Err(why) = trash::restore(file) {
+            let operation = Operation { source: file.original.clone(), target: file.file.clone(), ..failed.clone() };
+            unrestored.push((operation, why));
+        }
+    }
+
+    TransactionError {
+        failed:      failed,
+        cause:       cause,
+        rolled_back: applied.len() + parked.len() + trashed.len() - unrestored.len(),
+        unrestored:  unrestored,
+    }
+}
+
+/// Describes a failed transaction: which operation failed, and whether the operations before it were reverted.
+#[derive(Debug)]
+pub struct TransactionError {
+    pub failed:      Operation,
+    pub cause:       io::Error,
+    /// The number of applied operations that were successfully reverted.
+    pub rolled_back: usize,
+    /// Applied operations that could not be reverted, which must be restored by hand.
+    pub unrestored:  Vec<(Operation, io::Error)>,
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unable to rename {:?} to {:?}: {}", self.failed.source, self.failed.target, self.cause)?;
+        write!(f, "\nrolled back {} of {} completed renames", self.rolled_back, self.rolled_back + self.unrestored.len())?;
+        for &(ref operation, ref why) in &self.unrestored {
+            write!(f, "\nunable to restore {:?} from {:?}: {}", operation.source, operation.target, why)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for TransactionError {
+    fn description(&self) -> &str { "rename transaction failed" }
+}
+
+#[test]
+fn test_rollback() {
+    use backend::testing::TempDir;
+
+    let directory = TempDir::new("plan");
+    fs::write(directory.join("a.mkv"), b"a").unwrap();
+    fs::write(directory.join("b.mkv"), b"b").unwrap();
+
+    let operation = |source: &str, target: &str| Operation {
+        source: directory.join(source), target: directory.join(target), series_id: 0, season_no: 1, episode_no: 1
+    };
+
+    let mut plan = RenamePlan::new(Action::Rename);
+    plan.push(operation("a.mkv", "1x01.mkv"));
+    plan.push(operation("b.mkv", "1x02.mkv"));
+    plan.push(operation("missing.mkv", "1x03.mkv"));
+
+    let why = match plan.execute() {
+        Err(PlanError::Transaction(why)) => why,
+        _ => panic!("expected the transaction to fail")
+    };
+    assert_eq!(why.failed.source, directory.join("missing.mkv"));
+    assert_eq!(why.rolled_back, 2);
+    assert!(why.unrestored.is_empty());
+    assert!(directory.join("a.mkv").exists() && directory.join("b.mkv").exists());
+    assert!(!directory.join("1x01.mkv").exists() && !directory.join("1x02.mkv").exists());
+}
+
+#[test]
+fn test_overwrite() {
+    use backend::testing::TempDir;
+
+    for &action in &[Action::Copy, Action::Hardlink] {
+        let directory = TempDir::new("overwrite");
+        fs::write(directory.join("a.mkv"), b"new").unwrap();
+        fs::write(directory.join("1x01.mkv"), b"old").unwrap();
+        fs::write(directory.join("1x02.mkv"), b"old").unwrap();
+        let operation = |source: &str, target: &str, episode_no: u32| Operation {
+            source: directory.join(source), target: directory.join(target), series_id: 0, season_no: 1,
+            episode_no: episode_no
+        };
+
+        // A failed transaction restores the files which it overwrote.
+        let mut plan = RenamePlan::new(action);
+        plan.push(operation("a.mkv", "1x01.mkv", 1));
+        plan.push(operation("missing.mkv", "1x02.mkv", 2));
+        plan.resolve_conflicts(ConflictPolicy::Overwrite, |_| ConflictPolicy::Abort).unwrap();
+        match plan.execute() {
+            Err(PlanError::Transaction(why)) => assert_eq!(why.rolled_back, 3),
+            _ => panic!("expected the transaction to fail")
+        }
+        assert_eq!(fs::read(directory.join("1x01.mkv")).unwrap(), b"old");
+        assert_eq!(fs::read(directory.join("1x02.mkv")).unwrap(), b"old");
+
+        let mut plan = RenamePlan::new(action);
+        plan.push(operation("a.mkv", "1x01.mkv", 1));
+        plan.resolve_conflicts(ConflictPolicy::Overwrite, |_| ConflictPolicy::Abort).unwrap();
+        plan.execute().unwrap();
+        assert_eq!(fs::read(directory.join("a.mkv")).unwrap(), b"new");
+        assert_eq!(fs::read(directory.join("1x01.mkv")).unwrap(), b"new");
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 3);
+    }
+}
+
+#[cfg(test)]
+fn operation(source: &str, target: &str) -> Operation {
+    Operation { source: PathBuf::from(source), target: PathBuf::from(target), series_id: 0, season_no: 1, episode_no: 1 }
+}
+
+#[test]
+fn test_schedule_chain() {
+    let mut plan = RenamePlan::new(Action::Rename);
+    plan.push(operation("/tv/a", "/tv/b"));
+    plan.push(operation("/tv/b", "/tv/c"));
+    plan.push(operation("/tv/c", "/tv/d"));
+    plan.push(operation("/tv/e", "/tv/e"));
+    let steps = plan.schedule().unwrap().into_iter().map(|step| step.source).collect::<Vec<_>>();
+    assert_eq!(steps, vec![PathBuf::from("/tv/c"), PathBuf::from("/tv/b"), PathBuf::from("/tv/a")]);
+}
+
+#[test]
+fn test_schedule_cycle() {
+    let mut plan = RenamePlan::new(Action::Rename);
+    plan.push(operation("/tv/a", "/tv/b"));
+    plan.push(operation("/tv/b", "/tv/a"));
+    let steps = plan.schedule().unwrap();
+    assert_eq!(steps.len(), 3);
+    assert_eq!(steps[0].source, PathBuf::from("/tv/a"));
+    assert_eq!(steps[1].source, PathBuf::from("/tv/b"));
+    assert_eq!(steps[1].target, PathBuf::from("/tv/a"));
+    assert_eq!(steps[2].source, steps[0].target);
+    assert_eq!(steps[2].target, PathBuf::from("/tv/b"));
+}
+
+#[test]
+fn test_schedule_duplicates() {
+    let mut plan = RenamePlan::new(Action::Rename);
+    plan.push(operation("/tv/a", "/tv/c"));
+    plan.push(operation("/tv/b", "/tv/c"));
+    match plan.schedule() {
+        Err(PlanError::DuplicateTarget(target, _, _)) => assert_eq!(target, PathBuf::from("/tv/c")),
+        _ => panic!("expected a duplicate target")
+    }
+
+    let mut plan = RenamePlan::new(Action::Rename);
+    plan.push(operation("/tv/a", "/tv/b"));
+    plan.push(operation("/tv/a", "/tv/c"));
+    match plan.schedule() {
+        Err(PlanError::DuplicateSource(source)) => assert_eq!(source, PathBuf::from("/tv/a")),
+        _ => panic!("expected a duplicate source")
+    }
+}
+
+#[test]
+fn test_suffixed() {
+    assert_eq!(suffixed(Path::new("/tv/Show - 1x01.mkv"), "Show - 1x01", 2), PathBuf::from("/tv/Show - 1x01 (2).mkv"));
+    assert_eq!(suffixed(Path::new("/tv/Show - 1x01.en.forced.srt"), "Show - 1x01", 3),
+        PathBuf::from("/tv/Show - 1x01 (3).en.forced.srt"));
+    assert_eq!(suffixed(Path::new("/tv/other.nfo"), "Show - 1x01", 2), PathBuf::from("/tv/other (2).nfo"));
+}
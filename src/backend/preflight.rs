@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::Season;
+use super::junk::Junk;
+use super::provider::EpisodeInfo;
+
+/// Episode numbers that the names of several files claim.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Duplicate {
+    pub episode: u32,
+    pub files:   Vec<PathBuf>,
+}
+
+/// A file whose name gives a different episode number than the one it would be renamed to, which usually means that
+/// an earlier episode is missing or duplicated.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Misnumbered {
+    pub file:     PathBuf,
+    pub named:    u32,
+    pub assigned: u32,
+}
+
+/// Compares the episodes found in a season directory with the episode guide of the provider, before any file is
+/// renamed. Episodes are numbered in the order of their file names, so a single missing or extra file shifts the
+/// titles of every episode after it.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Report {
+    pub season:      u16,
+    /// Files that would be numbered beyond the episodes of the season.
+    pub extra:       Vec<PathBuf>,
+    /// Episodes of the season that no file is present for, going by the episode number in its name, or else by the
+    /// number that it would be renamed to.
+    pub missing:     Vec<u32>,
+    pub duplicates:  Vec<Duplicate>,
+    pub misnumbered: Vec<Misnumbered>,
+    /// Files whose names mark them as specials, which belong in season 0 rather than between regular episodes.
+    pub specials:    Vec<PathBuf>,
+    /// Files which are numbered as episodes, but which appear to be samples, trailers or bonus material.
+    pub junk:        Vec<Junk>,
+}
+
+impl Report {
+    /// Missing episodes are expected while a season is being collected, and are not a problem on their own.
+    pub fn has_problems(&self) -> bool {
+        !(self.extra.is_empty() && self.duplicates.is_empty() && self.misnumbered.is_empty()
+            && self.specials.is_empty() && self.junk.is_empty())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.has_problems() && self.missing.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "season {} does not match the episode guide:", self.season)?;
+        for file in &self.extra {
+            write!(f, "\n    extra file: {}", file.display())?;
+        }
+        if !self.missing.is_empty() {
+            let missing = self.missing.iter().map(|episode| episode.to_string()).collect::<Vec<_>>();
+            write!(f, "\n    missing episodes: {}", missing.join(", "))?;
+        }
+        for duplicate in &self.duplicates {
+            write!(f, "\n    episode {} is claimed by {} files", duplicate.episode, duplicate.files.len())?;
+            for file in &duplicate.files {
+                write!(f, "\n        {}", file.display())?;
+            }
+        }
+        for file in &self.misnumbered {
+            write!(f, "\n    {} is named as episode {}, but would become episode {}", file.file.display(),
+                file.named, file.assigned)?;
+        }
+        for file in &self.specials {
+            write!(f, "\n    special: {}", file.display())?;
+        }
+        for junk in &self.junk {
+            write!(f, "\n    not an episode, as it is {}: {}", junk.kind.description(), junk.path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the episodes of a season, which are given the episode numbers in `numbers`, against an episode guide.
+pub fn check(season: &Season, numbers: &[u32], guide: &[EpisodeInfo]) -> Report {
+    let in_season = guide.iter().filter(|episode| episode.season_no == season.season_no)
+        .map(|episode| episode.episode_no)
+        .collect::<Vec<u32>>();
+
+    let mut report = Report { season: season.season_no, ..Report::default() };
+    let mut claimed = BTreeMap::new();
+    let mut present = BTreeSet::new();
+    for (episode, &assigned) in season.episodes.iter().zip(numbers) {
+        present.insert(episode_hint(&episode.path).unwrap_or((season.season_no, assigned)));
+        if !in_season.contains(&assigned) {
+            report.extra.push(episode.path.clone());
+        }
+
+        if is_special(&episode.path) && season.season_no != 0 {
+            report.specials.push(episode.path.clone());
+        } else if let Some((_, named)) = episode_hint(&episode.path) {
+            claimed.entry(named).or_insert_with(Vec::new).push(episode.path.clone());
+            if named != assigned {
+                report.misnumbered.push(Misnumbered { file: episode.path.clone(), named: named, assigned: assigned });
+            }
+        }
+    }
+
+    // Junk is only among the episodes when the scan flags it rather than leaving it out.
+    report.junk = season.junk.iter()
+        .filter(|junk| season.episodes.iter().any(|episode| episode.path == junk.path))
+        .cloned()
+        .collect();
+
+    report.missing = in_season.into_iter()
+        .filter(|&episode| !present.contains(&(season.season_no, episode)))
+        .collect();
+    report.duplicates = claimed.into_iter()
+        .filter(|&(_, ref files)| files.len() > 1)
+        .map(|(episode, files)| Duplicate { episode: episode, files: files })
+        .collect();
+    report
+}
+
+/// Finds the season and episode numbers given by a file name, in the form of `S01E05` or `1x05`. Seasons which are
+/// numbered by year, such as `S2019E05`, and episodes beyond 999, such as `S01E1042`, are only recognized in the
+/// first form, as `1920x1080` is more likely to be a resolution.
+pub fn episode_hint(path: &Path) -> Option<(u16, u32)> {
+    let name = path.file_stem()?.to_str()?.to_lowercase();
+    let bytes = name.as_bytes();
+    (0..bytes.len())
+        .filter(|&start| start == 0 || !bytes[start - 1].is_ascii_alphanumeric())
+        .filter_map(|start| {
+            let (season, rest, limit) = match bytes[start] {
+                b's' => digits(&bytes[start + 1..], 4).map(|(season, rest)| (season, rest, 4))?,
+                _    => digits(&bytes[start..], 2).filter(|&(_, rest)| rest.first() == Some(&b'x'))
+                    .map(|(season, rest)| (season, rest, 3))?
+            };
+            let (episode, rest) = match rest.first() {
+                Some(&b'e') | Some(&b'x') => digits(&rest[1..], limit)?,
+                _ => return None
+            };
+            if rest.first().map_or(false, u8::is_ascii_digit) { return None }
+            Some((season as u16, episode))
+        })
+        .next()
+}
+
+/// Reads between one and `limit` digits from the start of the input.
+fn digits(input: &[u8], limit: usize) -> Option<(u32, &[u8])> {
+    let length = input.iter().take(limit + 1).take_while(|byte| byte.is_ascii_digit()).count();
+    if length == 0 || length > limit { return None }
+    let number = input[..length].iter().fold(0, |number, digit| number * 10 + (digit - b'0') as u32);
+    Some((number, &input[length..]))
+}
+
+/// Whether the name of a file marks it as a special, such as `S00E01` or `Show - Special.mkv`.
+fn is_special(path: &Path) -> bool {
+    let name = path.file_stem().map(|stem| stem.to_string_lossy().to_lowercase()).unwrap_or_default();
+    episode_hint(path).map_or(false, |(season, _)| season == 0)
+        || name.split(|character: char| !character.is_alphanumeric()).any(|word| word == "special" || word == "ova")
+}
+
+#[test]
+fn test_episode_hint() {
+    assert_eq!(episode_hint(Path::new("Show.S01E05.1080p.mkv")), Some((1, 5)));
+    assert_eq!(episode_hint(Path::new("show 2x13 - title.avi")), Some((2, 13)));
+    assert_eq!(episode_hint(Path::new("Show.1920x1080.mkv")), None);
+    assert_eq!(episode_hint(Path::new("Classes 1x.mkv")), None);
+    assert_eq!(episode_hint(Path::new("Show.S2019E05.mkv")), Some((2019, 5)));
+    assert_eq!(episode_hint(Path::new("Show - S01E1042.mkv")), Some((1, 1042)));
+    assert!(is_special(Path::new("Show - S00E02.mkv")));
+    assert!(is_special(Path::new("Show - Christmas Special.mkv")));
+}
+
+#[test]
+fn test_check() {
+    use super::Episode;
+    use super::junk::JunkKind;
+
+    let episode = |name: &str| Episode { path: PathBuf::from(name), sidecars: Vec::new() };
+    let guide = (1..5).map(|number| EpisodeInfo {
+        season_no: 1, episode_no: number, absolute_no: None, title: String::new(), first_aired: None, runtime: None
+    }).collect::<Vec<_>>();
+
+    // Episode 2 is missing, so the third file would be given the title of the second episode. The names of the files
+    // show which episodes of the guide are missing.
+    let season = Season {
+        season_no: 1, episodes: vec![episode("s01e01.mkv"), episode("s01e03.mkv")], junk: Vec::new()
+    };
+    let report = check(&season, &season.numbers_in_order(1), &guide);
+    assert_eq!(report.missing, vec![2, 4]);
+    assert_eq!(report.misnumbered, vec![
+        Misnumbered { file: PathBuf::from("s01e03.mkv"), named: 3, assigned: 2 }
+    ]);
+
+    // Numbered by their names, as the seasons of a mixed directory are, the files match the guide.
+    let report = check(&season, &season.numbers_by_name().unwrap(), &guide);
+    assert!(!report.has_problems());
+    assert_eq!(report.missing, vec![2, 4]);
+
+    let season = Season { season_no: 1, episodes: vec![
+        episode("a.mkv"), episode("b 1x02.mkv"), episode("c 1x02.mkv"), episode("Special.mkv"), episode("e.mkv")
+    ], junk: vec![
+        // The sample was left out of the episodes, and only the flagged file is reported.
+        Junk { path: PathBuf::from("sample.mkv"), kind: JunkKind::Sample },
+        Junk { path: PathBuf::from("e.mkv"), kind: JunkKind::Small }
+    ] };
+    let report = check(&season, &season.numbers_in_order(1), &guide);
+    assert_eq!(report.extra, vec![PathBuf::from("e.mkv")]);
+    assert_eq!(report.duplicates.len(), 1);
+    assert_eq!(report.specials, vec![PathBuf::from("Special.mkv")]);
+    assert_eq!(report.junk.iter().map(|junk| &junk.path).collect::<Vec<_>>(), vec![Path::new("e.mkv")]);
+    assert!(report.has_problems());
+}
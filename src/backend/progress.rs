@@ -0,0 +1,53 @@
+use std::collections::BTreeSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Records which series of a library have been renamed, so that a run over a large library may be interrupted and
+/// resumed where it stopped. The file lists the directory name of one series per line, and a line is appended as
+/// soon as each series has been renamed. Series which failed are not recorded, and are attempted again.
+pub struct Progress {
+    path:      PathBuf,
+    completed: BTreeSet<String>,
+}
+
+impl Progress {
+    /// Reads the series recorded by earlier runs. A file which does not exist yet records none.
+    pub fn open(path: &Path) -> io::Result<Progress> {
+        let completed = match fs::read_to_string(path) {
+            Ok(contents) => contents.lines().filter(|line| !line.is_empty()).map(String::from).collect(),
+            Err(ref why) if why.kind() == io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(why) => return Err(why)
+        };
+        Ok(Progress { path: path.to_path_buf(), completed: completed })
+    }
+
+    pub fn is_complete(&self, series: &str) -> bool {
+        self.completed.contains(series)
+    }
+
+    /// Records that a series has been renamed, syncing the file so that the record survives an interruption.
+    pub fn complete(&mut self, series: &str) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", series)?;
+        file.sync_data()?;
+        self.completed.insert(series.to_owned());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_progress() {
+    use backend::testing::TempDir;
+
+    let directory = TempDir::new("progress");
+    let path = directory.join("progress");
+    let mut progress = Progress::open(&path).unwrap();
+    assert!(!progress.is_complete("Firefly"));
+    progress.complete("Firefly").unwrap();
+    progress.complete("The Wire").unwrap();
+
+    let progress = Progress::open(&path).unwrap();
+    assert!(progress.is_complete("Firefly") && progress.is_complete("The Wire"));
+    assert!(!progress.is_complete("Fire"));
+}
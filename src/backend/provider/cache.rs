@@ -0,0 +1,126 @@
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde_json;
+
+use super::{EpisodeInfo, Ordering, Provider, ProviderError, SeriesInfo};
+
+/// How long, in seconds, an episode guide is kept before it is requested again.
+const MAX_AGE: u64 = 24 * 60 * 60;
+
+/// Keeps the episode guides of a provider under `$XDG_CACHE_HOME/tv-renamer`, so that a series which is renamed one
+/// season at a time, or previewed before it is renamed, has its guide requested once a day.
+pub struct Cache<P: Provider> {
+    provider:    P,
+    directory:   Option<PathBuf>,
+    /// Requests every guide again, replacing the cached copy.
+    pub refresh: bool,
+}
+
+impl<P: Provider> Cache<P> {
+    pub fn new(provider: P) -> Cache<P> {
+        Cache { provider: provider, directory: cache_directory(), refresh: false }
+    }
+
+    /// Guides are stored as `<provider>/<series>-<ordering>-<language>.json` within the cache directory.
+    fn path(&self, series_id: u32, ordering: Ordering) -> Option<PathBuf> {
+        self.directory.as_ref().map(|directory| {
+            let name = format!("{}-{}-{}.json", series_id, ordering.as_str(), self.provider.language());
+            directory.join(self.provider.name()).join(name)
+        })
+    }
+}
+
+impl<P: Provider> Provider for Cache<P> {
+    fn name(&self) -> &'static str { self.provider.name() }
+
+    fn language(&self) -> &str { self.provider.language() }
+
+    fn search(&self, name: &str) -> Result<Vec<SeriesInfo>, ProviderError> {
+        self.provider.search(name)
+    }
+
+    fn episodes(&self, series_id: u32, ordering: Ordering) -> Result<Vec<EpisodeInfo>, ProviderError> {
+        let path = match self.path(series_id, ordering) {
+            Some(path) => path,
+            None => return self.provider.episodes(series_id, ordering)
+        };
+
+        if !self.refresh && age(&path).map_or(false, |age| age < MAX_AGE) {
+            if let Ok(guide) = load(&path) { return Ok(guide) }
+        }
+
+        match self.provider.episodes(series_id, ordering) {
+            Ok(guide) => {
+                // Failing to cache the guide only means that it will be requested again next time.
+                let _ = save(&path, &guide);
+                Ok(guide)
+            },
+            // An outdated guide is better than none when the provider cannot be reached.
+            Err(ProviderError::Request(why)) if !self.refresh => load(&path).map_err(|_| ProviderError::Request(why)),
+            Err(why) => Err(why)
+        }
+    }
+}
+
+/// The directory where episode guides are cached: `$XDG_CACHE_HOME/tv-renamer`, which defaults to
+/// `~/.cache/tv-renamer`.
+pub fn cache_directory() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::home_dir().map(|home| home.join(".cache")))
+        .map(|cache| cache.join("tv-renamer"))
+}
+
+/// Seconds since the file was last written.
+fn age(path: &Path) -> Option<u64> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map(|age| age.as_secs())
+}
+
+fn load(path: &Path) -> Result<Vec<EpisodeInfo>, ProviderError> {
+    Ok(serde_json::from_reader(File::open(path)?)?)
+}
+
+fn save(path: &Path, guide: &[EpisodeInfo]) -> Result<(), ProviderError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(serde_json::to_writer(File::create(path)?, guide)?)
+}
+
+#[test]
+fn test_cache() {
+    use std::cell::Cell;
+    use backend::testing::TempDir;
+
+    struct Counter(Cell<usize>);
+
+    impl Provider for Counter {
+        fn name(&self) -> &'static str { "counter" }
+        fn language(&self) -> &str { "eng" }
+        fn search(&self, _: &str) -> Result<Vec<SeriesInfo>, ProviderError> { Ok(Vec::new()) }
+        fn episodes(&self, _: u32, _: Ordering) -> Result<Vec<EpisodeInfo>, ProviderError> {
+            self.0.set(self.0.get() + 1);
+            Ok(vec![EpisodeInfo {
+                season_no: 1, episode_no: 1, absolute_no: Some(1), title: String::from("Pilot"), first_aired: None,
+                runtime: None
+            }])
+        }
+    }
+
+    let directory = TempDir::new("cache");
+    let mut cache = Cache { provider: Counter(Cell::new(0)), directory: Some(directory.to_path_buf()), refresh: false };
+    let guide = cache.episodes(1, Ordering::Aired).unwrap();
+    assert_eq!(cache.episodes(1, Ordering::Aired).unwrap(), guide);
+    assert_eq!(cache.provider.0.get(), 1);
+    assert!(directory.join("counter/1-aired-eng.json").exists());
+
+    cache.episodes(1, Ordering::Dvd).unwrap();
+    cache.refresh = true;
+    cache.episodes(1, Ordering::Aired).unwrap();
+    assert_eq!(cache.provider.0.get(), 3);
+}
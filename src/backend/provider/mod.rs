@@ -0,0 +1,149 @@
+pub mod cache;
+pub mod tvdb;
+
+use std::io;
+use std::str::FromStr;
+
+use serde_json;
+
+use super::config::Settings;
+
+pub use self::cache::Cache;
+pub use self::tvdb::Tvdb;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum ProviderError {
+        Request(err: String) {
+            display("unable to reach the provider: {}", err)
+        }
+        Status(status: u16) {
+            display("the provider responded with status {}", status)
+        }
+        Format(err: serde_json::Error) {
+            from()
+            display("the response of the provider could not be read: {}", err)
+        }
+        Cache(err: io::Error) {
+            from()
+            display("unable to access the episode cache: {}", err)
+        }
+        MissingApiKey {
+            display("no TVDB API key is configured; set $TVDB_API_KEY or `api-key` in config.toml")
+        }
+        LoginFailed {
+            display("TVDB rejected the API key or subscriber PIN; set `api-key` and `pin` in the configuration file, \
+                or $TVDB_API_KEY and $TVDB_PIN")
+        }
+        SeriesNotFound(series: String) {
+            display("invalid TV series: {}", series)
+        }
+    }
+}
+
+/// Determines how the episodes of a series are numbered, as providers may list several orderings of a series.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ordering {
+    /// The order in which the episodes first aired.
+    Aired,
+    /// The order of the episodes on DVD or Blu-ray.
+    Dvd,
+    /// A single season which numbers every episode of the series in order, as is common for anime.
+    Absolute,
+    /// An alternate order, such as a director's cut.
+    Alternate,
+    /// The order in which the episodes aired in another region.
+    Regional,
+}
+
+impl Ordering {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Ordering::Aired     => "aired",
+            Ordering::Dvd       => "dvd",
+            Ordering::Absolute  => "absolute",
+            Ordering::Alternate => "alternate",
+            Ordering::Regional  => "regional",
+        }
+    }
+}
+
+impl FromStr for Ordering {
+    type Err = ();
+    fn from_str(ordering: &str) -> Result<Ordering, ()> {
+        match ordering {
+            "aired"     => Ok(Ordering::Aired),
+            "dvd"       => Ok(Ordering::Dvd),
+            "absolute"  => Ok(Ordering::Absolute),
+            "alternate" => Ok(Ordering::Alternate),
+            "regional"  => Ok(Ordering::Regional),
+            _           => Err(())
+        }
+    }
+}
+
+/// A series which matched a search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeriesInfo {
+    pub id:   u32,
+    pub name: String,
+    /// The year in which the series first aired, if known.
+    pub year: Option<String>,
+}
+
+/// A single entry of an episode guide.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EpisodeInfo {
+    /// The number of the season, which may be a year, such as 2019.
+    pub season_no:   u16,
+    pub episode_no:  u32,
+    /// The number of the episode counted from the start of the series, if the provider knows it.
+    pub absolute_no: Option<u32>,
+    pub title:       String,
+    /// The date on which the episode first aired, as `YYYY-MM-DD`.
+    pub first_aired: Option<String>,
+    /// The length of the episode in minutes, if the provider knows it. Guides cached before runtimes were recorded
+    /// have none.
+    #[serde(default)]
+    pub runtime:     Option<u32>,
+}
+
+/// A source of series and episode metadata.
+pub trait Provider {
+    /// The name of the provider, which names its directory in the cache.
+    fn name(&self) -> &'static str;
+
+    /// The language of the titles in its episode guides.
+    fn language(&self) -> &str;
+
+    /// Searches for series with the given name, with the best match first.
+    fn search(&self, name: &str) -> Result<Vec<SeriesInfo>, ProviderError>;
+
+    /// Lists every episode of a series in the given ordering, sorted by season and episode.
+    fn episodes(&self, series_id: u32, ordering: Ordering) -> Result<Vec<EpisodeInfo>, ProviderError>;
+}
+
+/// The provider used by both frontends: TVDB, with its episode guides cached.
+pub fn open(settings: &Settings) -> Cache<Tvdb> {
+    Cache::new(Tvdb::new(settings.api_key.as_deref(), settings.pin.as_deref(), &settings.language))
+}
+
+/// Finds the ID of the series which best matches the given name.
+pub fn lookup<P: Provider>(provider: &P, name: &str) -> Result<u32, ProviderError> {
+    provider.search(name)?.into_iter().next()
+        .map(|series| series.id)
+        .ok_or_else(|| ProviderError::SeriesNotFound(name.to_owned()))
+}
+
+/// Finds an episode within an episode guide.
+pub fn find(guide: &[EpisodeInfo], season_no: u16, episode_no: u32) -> Option<&EpisodeInfo> {
+    guide.iter().find(|episode| episode.season_no == season_no && episode.episode_no == episode_no)
+}
+
+#[test]
+fn test_ordering() {
+    for &ordering in &[Ordering::Aired, Ordering::Dvd, Ordering::Absolute, Ordering::Alternate, Ordering::Regional] {
+        assert_eq!(ordering.as_str().parse::<Ordering>(), Ok(ordering));
+    }
+    assert!("official".parse::<Ordering>().is_err());
+}
@@ -0,0 +1,362 @@
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde_json;
+use ureq;
+
+use super::cache::cache_directory;
+use super::{EpisodeInfo, Ordering, Provider, ProviderError, SeriesInfo};
+
+/// The address of version 4 of the TVDB API.
+const API_URL: &'static str = "https://api4.thetvdb.com/v4";
+
+/// The language in which episode titles are requested by default, as an ISO 639-2 code.
+pub const LANGUAGE: &'static str = "eng";
+
+/// A client of version 4 of the TVDB API.
+pub struct Tvdb {
+    url:        String,
+    /// The API key, which must be configured, as TVDB no longer accepts keys shared by every user of a program.
+    key:        Option<String>,
+    pin:        Option<String>,
+    /// The language in which episode titles are requested.
+    language:   String,
+    /// The bearer token returned by logging in, which is requested by the first request that needs it.
+    token:      RefCell<Option<String>>,
+    /// Where the bearer token is kept between runs, as it remains valid for a month.
+    token_path: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    data:  T,
+    #[serde(default)]
+    links: Links,
+}
+
+/// Episode guides are split into pages, of which the last has no link to a next page.
+#[derive(Default, Deserialize)]
+struct Links {
+    next: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Login<'a> {
+    apikey: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pin:    Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct Token {
+    token: String,
+}
+
+/// A cached bearer token, along with the credentials that it was issued for. The file is readable only by its owner,
+/// so the credentials are kept as they are.
+#[derive(Serialize, Deserialize)]
+struct CachedToken {
+    url:   String,
+    key:   String,
+    pin:   Option<String>,
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct SearchResult {
+    tvdb_id: String,
+    #[serde(default)]
+    name:    String,
+    year:    Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EpisodePage {
+    #[serde(default)]
+    episodes: Vec<Episode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Episode {
+    #[serde(default)]
+    season_number:   u32,
+    #[serde(default)]
+    number:          u32,
+    absolute_number: Option<u32>,
+    name:            Option<String>,
+    aired:           Option<String>,
+    runtime:         Option<u32>,
+}
+
+impl Episode {
+    /// Episodes whose seasons are numbered beyond 65535 are left out, as no season directory could name them.
+    fn into_info(self) -> Option<EpisodeInfo> {
+        if self.season_number > u16::max_value() as u32 {
+            return None
+        }
+
+        Some(EpisodeInfo {
+            season_no:   self.season_number as u16,
+            episode_no:  self.number,
+            absolute_no: self.absolute_number.filter(|&number| number != 0),
+            title:       self.name.unwrap_or_default(),
+            first_aired: self.aired.filter(|date| !date.is_empty()),
+            runtime:     self.runtime.filter(|&runtime| runtime != 0),
+        })
+    }
+}
+
+impl Tvdb {
+    pub fn new(key: Option<&str>, pin: Option<&str>, language: &str) -> Tvdb {
+        Tvdb {
+            url:        API_URL.to_owned(),
+            key:        key.map(String::from),
+            pin:        pin.map(String::from),
+            language:   language.to_owned(),
+            token:      RefCell::new(None),
+            token_path: cache_directory().map(|directory| directory.join("tvdb/token.json")),
+        }
+    }
+
+    /// Returns the token of an earlier login, if one was issued for the same credentials, or else logs in.
+    fn token(&self) -> Result<String, ProviderError> {
+        if let Some(ref token) = *self.token.borrow() {
+            return Ok(token.clone())
+        }
+
+        let token = match self.cached_token() {
+            Some(token) => token,
+            None => self.login()?
+        };
+        *self.token.borrow_mut() = Some(token.clone());
+        Ok(token)
+    }
+
+    /// Exchanges the API key, and the subscriber PIN if there is one, for a bearer token.
+    fn login(&self) -> Result<String, ProviderError> {
+        let key = self.key.as_ref().ok_or(ProviderError::MissingApiKey)?;
+        let login = serde_json::to_string(&Login { apikey: key, pin: self.pin.as_deref() })?;
+        let response = ureq::post(&[&self.url, "/login"].concat())
+            .set("Content-Type", "application/json")
+            .send_string(&login);
+        let token = match read::<Token>(response) {
+            Ok(response) => response.data.token,
+            Err(ProviderError::Status(401)) => return Err(ProviderError::LoginFailed),
+            Err(why) => return Err(why)
+        };
+
+        // Failing to keep the token only means logging in again next time.
+        let _ = self.save_token(key, &token);
+        Ok(token)
+    }
+
+    fn cached_token(&self) -> Option<String> {
+        let path = self.token_path.as_ref()?;
+        let cached = serde_json::from_reader::<_, CachedToken>(File::open(path).ok()?).ok()?;
+        let issued = cached.url == self.url && Some(&cached.key) == self.key.as_ref() && cached.pin == self.pin;
+        if issued { Some(cached.token) } else { None }
+    }
+
+    /// Saves the token where only the current user may read it. Other platforms rely on the permissions of the cache
+    /// directory, which is within the user's profile.
+    fn save_token(&self, key: &str, token: &str) -> Result<(), ProviderError> {
+        if let Some(ref path) = self.token_path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut options = OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            options.mode(0o600);
+            let file = options.open(path)?;
+            let cached = CachedToken {
+                url:   self.url.clone(),
+                key:   key.to_owned(),
+                pin:   self.pin.clone(),
+                token: token.to_owned(),
+            };
+            serde_json::to_writer(file, &cached)?;
+        }
+        Ok(())
+    }
+
+    /// Sends a request with the bearer token. Tokens expire after a month, so a request which is refused is sent
+    /// once more with a new token.
+    fn request<T: DeserializeOwned>(&self, request: ureq::Request) -> Result<Response<T>, ProviderError> {
+        let token = self.token()?;
+        match read(request.clone().set("Authorization", &["Bearer ", &token].concat()).call()) {
+            Err(ProviderError::Status(401)) => {
+                let token = self.login()?;
+                *self.token.borrow_mut() = Some(token.clone());
+                read(request.set("Authorization", &["Bearer ", &token].concat()).call())
+            },
+            response => response
+        }
+    }
+}
+
+impl Provider for Tvdb {
+    fn name(&self) -> &'static str { "tvdb" }
+
+    fn language(&self) -> &str { &self.language }
+
+    fn search(&self, name: &str) -> Result<Vec<SeriesInfo>, ProviderError> {
+        let request = ureq::get(&[&self.url, "/search"].concat()).query("query", name).query("type", "series");
+        let results = self.request::<Vec<SearchResult>>(request)?.data;
+        Ok(results.into_iter()
+            .filter_map(|series| series.tvdb_id.parse::<u32>().ok().map(|id| SeriesInfo {
+                id: id, name: series.name, year: series.year
+            }))
+            .collect())
+    }
+
+    fn episodes(&self, series_id: u32, ordering: Ordering) -> Result<Vec<EpisodeInfo>, ProviderError> {
+        let url = format!("{}/series/{}/episodes/{}/{}", self.url, series_id, season_type(ordering), self.language);
+        let mut guide = Vec::new();
+        let mut page = 0;
+        loop {
+            let response = self.request::<EpisodePage>(ureq::get(&url).query("page", &page.to_string()))?;
+            guide.extend(response.data.episodes.into_iter().filter_map(Episode::into_info));
+            if response.links.next.is_none() { break }
+            page += 1;
+        }
+
+        guide.sort_by(|a, b| (a.season_no, a.episode_no).cmp(&(b.season_no, b.episode_no)));
+        Ok(guide)
+    }
+}
+
+/// The name that TVDB gives to each ordering.
+fn season_type(ordering: Ordering) -> &'static str {
+    match ordering {
+        Ordering::Aired     => "default",
+        Ordering::Dvd       => "dvd",
+        Ordering::Absolute  => "absolute",
+        Ordering::Alternate => "alternate",
+        Ordering::Regional  => "regional",
+    }
+}
+
+fn read<T: DeserializeOwned>(response: Result<ureq::Response, ureq::Error>) -> Result<Response<T>, ProviderError> {
+    match response {
+        Ok(response) => Ok(serde_json::from_reader(response.into_reader())?),
+        Err(ureq::Error::Status(status, _)) => Err(ProviderError::Status(status)),
+        Err(why) => Err(ProviderError::Request(why.to_string()))
+    }
+}
+
+#[test]
+fn test_episode_page() {
+    let page = r#"{"status": "success", "data": {"series": {"id": 81189}, "episodes": [
+        {"id": 1, "seasonNumber": 1, "number": 2, "absoluteNumber": 2, "name": "Cat's in the Bag...",
+            "aired": "2008-01-27", "runtime": 48},
+        {"id": 2, "seasonNumber": 0, "number": 1, "absoluteNumber": 0, "name": null, "aired": ""},
+        {"id": 3, "seasonNumber": 2008, "number": 70000, "name": "By Year"},
+        {"id": 4, "seasonNumber": 70000, "number": 1, "name": "Out of Range"}
+    ]}, "links": {"next": null}}"#;
+
+    let response = serde_json::from_str::<Response<EpisodePage>>(page).unwrap();
+    assert!(response.links.next.is_none());
+    let guide = response.data.episodes.into_iter().filter_map(Episode::into_info).collect::<Vec<_>>();
+    assert_eq!(guide, vec![
+        EpisodeInfo {
+            season_no: 1, episode_no: 2, absolute_no: Some(2), title: String::from("Cat's in the Bag..."),
+            first_aired: Some(String::from("2008-01-27")), runtime: Some(48)
+        },
+        EpisodeInfo {
+            season_no: 0, episode_no: 1, absolute_no: None, title: String::new(), first_aired: None, runtime: None
+        },
+        EpisodeInfo {
+            season_no: 2008, episode_no: 70000, absolute_no: None, title: String::from("By Year"), first_aired: None,
+            runtime: None
+        },
+    ]);
+}
+
+/// Serves HTTP requests on a local port, answering each with the status and body returned by the handler, which is
+/// given the request line, the bearer token and the body of the request.
+#[cfg(test)]
+fn mock_server<F>(handler: F) -> String where F: Fn(&str, &str, &str) -> (u16, String) + Send + 'static {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || for stream in listener.incoming() {
+        let mut stream = stream.unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let (mut request, mut token, mut length) = (String::new(), String::new(), 0);
+        reader.read_line(&mut request).unwrap();
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            let header = header.trim_end().to_owned();
+            if header.is_empty() { break }
+            let lowercase = header.to_lowercase();
+            if lowercase.starts_with("authorization: bearer ") { token = header[22..].to_owned(); }
+            if lowercase.starts_with("content-length: ") { length = header[16..].parse().unwrap(); }
+        }
+
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        let (status, body) = handler(request.trim_end(), &token, &String::from_utf8_lossy(&body));
+        let _ = write!(stream, "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+            Connection: close\r\n\r\n{}", status, body.len(), body);
+    });
+    url
+}
+
+#[test]
+fn test_login() {
+    use std::sync::{Arc, Mutex};
+    use backend::testing::TempDir;
+
+    // The first token has expired by the time that it is used, and the second is accepted.
+    let logins = Arc::new(Mutex::new(Vec::new()));
+    let url = {
+        let logins = logins.clone();
+        mock_server(move |request, token, body| {
+            if request.starts_with("POST /login ") {
+                let mut logins = logins.lock().unwrap();
+                logins.push(body.to_owned());
+                (200, format!(r#"{{"status": "success", "data": {{"token": "token-{}"}}}}"#, logins.len()))
+            } else if request.starts_with("GET /search?") && token == "token-2" {
+                (200, String::from(r#"{"status": "success", "data": [{"tvdb_id": "81189", "name": "Breaking Bad"}]}"#))
+            } else {
+                (401, String::from(r#"{"status": "failure", "message": "Unauthorized"}"#))
+            }
+        })
+    };
+
+    let directory = TempDir::new("token");
+    let token_path = directory.join("token.json");
+    let tvdb = |pin: Option<&str>| Tvdb {
+        url: url.clone(), key: Some(String::from("key")), pin: pin.map(String::from), language: String::from("eng"),
+        token: RefCell::new(None), token_path: Some(token_path.clone())
+    };
+
+    let series = tvdb(Some("1234")).search("Breaking Bad").unwrap();
+    assert_eq!(series[0].id, 81189);
+    assert_eq!(*logins.lock().unwrap(), vec![r#"{"apikey":"key","pin":"1234"}"#; 2]);
+
+    // The refreshed token is used by later runs, until the credentials change.
+    tvdb(Some("1234")).search("Breaking Bad").unwrap();
+    assert_eq!(logins.lock().unwrap().len(), 2);
+    assert!(tvdb(None).search("Breaking Bad").is_err());
+    assert_eq!(logins.lock().unwrap().last().unwrap(), r#"{"apikey":"key"}"#);
+
+    // Without a key, nothing is sent, not even a token that was issued for another key.
+    let count = logins.lock().unwrap().len();
+    let tvdb = Tvdb { key: None, ..tvdb(None) };
+    match tvdb.search("Breaking Bad") {
+        Err(ProviderError::MissingApiKey) => (),
+        result => panic!("expected a missing key, found {:?}", result.map(|_| ()))
+    }
+    assert_eq!(logins.lock().unwrap().len(), count);
+}
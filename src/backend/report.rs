@@ -0,0 +1,245 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json;
+
+use super::ScanDir;
+use super::date::civil_from_days;
+use super::preflight::episode_hint;
+use super::provider::EpisodeInfo;
+
+/// The formats that a library report may be written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportFormat { Markdown, Html, Json }
+
+impl FromStr for ReportFormat {
+    type Err = ();
+    fn from_str(format: &str) -> Result<ReportFormat, ()> {
+        match format {
+            "markdown" => Ok(ReportFormat::Markdown),
+            "html"     => Ok(ReportFormat::Html),
+            "json"     => Ok(ReportFormat::Json),
+            _          => Err(())
+        }
+    }
+}
+
+/// An episode of the guide which has no file in the library.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Absent {
+    pub season:      u16,
+    pub episode:     u32,
+    pub title:       String,
+    pub first_aired: Option<String>,
+}
+
+/// Compares the episodes of a series directory with the episode guide of the provider.
+#[derive(Debug, Default, Serialize)]
+pub struct SeriesReport {
+    pub series:    String,
+    pub directory: PathBuf,
+    pub series_id: Option<u32>,
+    /// Episodes which have aired, but have no file.
+    pub missing:   Vec<Absent>,
+    /// Episodes which have yet to air, or have no air date.
+    pub unaired:   Vec<Absent>,
+    /// Files whose episode is not in the guide.
+    pub extra:     Vec<PathBuf>,
+    /// Why the series could not be compared, such as it not being found on TVDB.
+    pub error:     Option<String>,
+}
+
+impl SeriesReport {
+    pub fn new(series: String, directory: PathBuf) -> SeriesReport {
+        SeriesReport { series: series, directory: directory, ..SeriesReport::default() }
+    }
+
+    /// Compares the scanned episodes with the guide, as of the given date. Episodes are identified by the season and
+    /// episode numbers in their file names, such as `S01E05`, and otherwise by their position in their season, as
+    /// they would be numbered when renamed. Specials are only expected of series which have any.
+    pub fn compare(&mut self, scanned: &ScanDir, episode_start: u32, guide: &[EpisodeInfo], today: &str) {
+        let seasons = match *scanned {
+            ScanDir::Episodes(ref season) => vec![(season, episode_start)],
+            ScanDir::Seasons(ref seasons) | ScanDir::Mixed(ref seasons) => {
+                seasons.iter().map(|season| (season, 1)).collect()
+            }
+        };
+
+        let mut present = BTreeSet::new();
+        for (season, episode_start) in seasons {
+            for (index, episode) in season.episodes.iter().enumerate() {
+                let number = episode_hint(&episode.path)
+                    .unwrap_or((season.season_no, episode_start.saturating_add(index as u32)));
+                let known = guide.iter()
+                    .any(|entry| (entry.season_no, entry.episode_no) == number);
+                if !known { self.extra.push(episode.path.clone()); }
+                present.insert(number);
+            }
+        }
+
+        let specials = present.iter().any(|&(season, _)| season == 0);
+        for entry in guide {
+            if present.contains(&(entry.season_no, entry.episode_no)) || (entry.season_no == 0 && !specials) {
+                continue
+            }
+
+            let absent = Absent {
+                season:      entry.season_no,
+                episode:     entry.episode_no,
+                title:       entry.title.clone(),
+                first_aired: entry.first_aired.clone(),
+            };
+
+            // Dates are compared as `YYYY-MM-DD` strings, which sort chronologically.
+            match entry.first_aired {
+                Some(ref date) if date.as_str() <= today => self.missing.push(absent),
+                _ => self.unaired.push(absent)
+            }
+        }
+    }
+}
+
+/// Writes the reports of every series in the given format.
+pub fn render(format: ReportFormat, reports: &[SeriesReport]) -> String {
+    match format {
+        ReportFormat::Markdown => markdown(reports),
+        ReportFormat::Html     => html(reports),
+        ReportFormat::Json     => serde_json::to_string_pretty(reports).unwrap_or_default() + "\n"
+    }
+}
+
+/// Today's date in UTC, as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs() / 86400).unwrap_or(0);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn label(absent: &Absent) -> String {
+    format!("S{:02}E{:02}", absent.season, absent.episode)
+}
+
+fn markdown(reports: &[SeriesReport]) -> String {
+    let cell = |text: &str| text.replace('|', "\\|");
+    let mut output = String::from("# Library Report\n\n| Series | Missing | Unaired | Extra |\n|---|---:|---:|---:|\n");
+    for report in reports {
+        output.push_str(&match report.error {
+            Some(_) => format!("| {} | ? | ? | ? |\n", cell(&report.series)),
+            None => format!("| {} | {} | {} | {} |\n", cell(&report.series), report.missing.len(),
+                report.unaired.len(), report.extra.len())
+        });
+    }
+
+    for report in reports {
+        output.push_str(&format!("\n## {}\n\n", report.series));
+        if let Some(ref why) = report.error {
+            output.push_str(&format!("Unable to compare `{}` with TVDB: {}\n", report.directory.display(), why));
+            continue
+        }
+        if report.missing.is_empty() && report.unaired.is_empty() && report.extra.is_empty() {
+            output.push_str("Every episode is present.\n");
+            continue
+        }
+
+        for &(heading, episodes) in &[("Missing", &report.missing), ("Unaired", &report.unaired)] {
+            if episodes.is_empty() { continue }
+            output.push_str(&format!("### {}\n\n", heading));
+            for absent in episodes.iter() {
+                let aired = absent.first_aired.as_ref().map_or_else(String::new, |date| format!(" ({})", date));
+                output.push_str(&format!("- {} {}{}\n", label(absent), absent.title, aired));
+            }
+            output.push('\n');
+        }
+
+        if !report.extra.is_empty() {
+            output.push_str("### Extra\n\n");
+            for path in &report.extra {
+                output.push_str(&format!("- `{}`\n", path.display()));
+            }
+            output.push('\n');
+        }
+    }
+    output
+}
+
+fn html(reports: &[SeriesReport]) -> String {
+    let mut output = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>Library Report</title>\n</head>\n<body>\n<h1>Library Report</h1>\n<table>\n\
+        <tr><th>Series</th><th>Missing</th><th>Unaired</th><th>Extra</th></tr>\n");
+    for report in reports {
+        output.push_str(&match report.error {
+            Some(_) => format!("<tr><td>{}</td><td>?</td><td>?</td><td>?</td></tr>\n", escape(&report.series)),
+            None => format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n", escape(&report.series),
+                report.missing.len(), report.unaired.len(), report.extra.len())
+        });
+    }
+    output.push_str("</table>\n");
+
+    for report in reports {
+        output.push_str(&format!("<h2>{}</h2>\n", escape(&report.series)));
+        if let Some(ref why) = report.error {
+            output.push_str(&format!("<p>Unable to compare <code>{}</code> with TVDB: {}</p>\n",
+                escape(&report.directory.to_string_lossy()), escape(why)));
+            continue
+        }
+        if report.missing.is_empty() && report.unaired.is_empty() && report.extra.is_empty() {
+            output.push_str("<p>Every episode is present.</p>\n");
+            continue
+        }
+
+        for &(heading, episodes) in &[("Missing", &report.missing), ("Unaired", &report.unaired)] {
+            if episodes.is_empty() { continue }
+            output.push_str(&format!("<h3>{}</h3>\n<ul>\n", heading));
+            for absent in episodes.iter() {
+                let aired = absent.first_aired.as_ref().map_or_else(String::new, |date| format!(" ({})", date));
+                output.push_str(&format!("<li>{} {}{}</li>\n", label(absent), escape(&absent.title), aired));
+            }
+            output.push_str("</ul>\n");
+        }
+
+        if !report.extra.is_empty() {
+            output.push_str("<h3>Extra</h3>\n<ul>\n");
+            for path in &report.extra {
+                output.push_str(&format!("<li><code>{}</code></li>\n", escape(&path.to_string_lossy())));
+            }
+            output.push_str("</ul>\n");
+        }
+    }
+    output.push_str("</body>\n</html>\n");
+    output
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[test]
+fn test_compare() {
+    use super::{Episode, Season};
+
+    let entry = |season: u16, episode: u32, aired: Option<&str>| EpisodeInfo {
+        season_no: season, episode_no: episode, absolute_no: None, title: format!("Episode {}", episode),
+        first_aired: aired.map(String::from), runtime: None
+    };
+    let guide = vec![
+        entry(0, 1, Some("2010-12-25")), entry(1, 1, Some("2010-01-01")), entry(1, 2, Some("2010-01-08")),
+        entry(1, 3, Some("2010-01-15")), entry(2, 1, Some("2030-01-01")), entry(2, 2, None)
+    ];
+    let episode = |name: &str| Episode { path: PathBuf::from(name), sidecars: Vec::new() };
+
+    // The second file is identified by its name, and the first by its position.
+    let scanned = ScanDir::Episodes(Season { season_no: 1, episodes: vec![
+        episode("Pilot.mkv"), episode("Show S01E03.mkv"), episode("Show S03E01.mkv")
+    ], junk: Vec::new() });
+    let mut report = SeriesReport::new(String::from("Show"), PathBuf::from("Show"));
+    report.compare(&scanned, 1, &guide, "2020-06-01");
+    assert_eq!(report.missing.iter().map(|absent| (absent.season, absent.episode)).collect::<Vec<_>>(), vec![(1, 2)]);
+    assert_eq!(report.unaired.len(), 2);
+    assert_eq!(report.extra, vec![PathBuf::from("Show S03E01.mkv")]);
+
+    let markdown = render(ReportFormat::Markdown, &[report]);
+    assert!(markdown.contains("| Show | 1 | 2 | 1 |"));
+    assert!(markdown.contains("- S01E02 Episode 2 (2010-01-08)"));
+}
@@ -0,0 +1,160 @@
+use super::Season;
+use super::media;
+use super::preflight::episode_hint;
+use super::provider::{self, EpisodeInfo};
+
+/// The least confidence with which the episodes of a season are numbered by their runtimes, rather than by the order
+/// in which they were sorted.
+pub const MIN_CONFIDENCE: f64 = 0.5;
+
+/// How far apart, in seconds, the cost of the best assignment and of the best alternative must be for a file to be
+/// matched with a confidence of one half.
+const SEPARATION: f64 = 60.0;
+
+/// The least difference, in seconds, by which a duration may differ from a runtime before it no longer matches. The
+/// runtimes of guides are rounded to minutes, and do not always count the credits.
+const TOLERANCE: f64 = 90.0;
+
+/// An assignment of the files of a season to its episodes, by comparing the durations of the files with the runtimes
+/// of the episodes.
+#[derive(Debug, PartialEq)]
+pub struct RuntimeMatch {
+    /// The position of the episode of each file, in the order that the files were sorted in.
+    pub positions:  Vec<usize>,
+    /// How likely the assignment is to be right, from 0 to 1. Files of similar durations, or whose durations are far
+    /// from every runtime, lower the confidence.
+    pub confidence: f64,
+}
+
+impl RuntimeMatch {
+    pub fn is_confident(&self) -> bool {
+        self.confidence >= MIN_CONFIDENCE
+    }
+
+    /// Reorders the episodes of the season, so that they are numbered as they were assigned.
+    pub fn apply(&self, season: &mut Season) {
+        let mut episodes = self.positions.iter().cloned().zip(season.episodes.drain(..)).collect::<Vec<_>>();
+        episodes.sort_by_key(|&(position, _)| position);
+        season.episodes = episodes.into_iter().map(|(_, episode)| episode).collect();
+    }
+}
+
+/// Proposes an assignment of the files of a season to the episodes that they would be numbered as, from
+/// `episode_start`, by their durations. There is nothing to propose when the names of the files give their episodes,
+/// or when the duration of a file or the runtime of an episode is unknown.
+pub fn propose(season: &Season, episode_start: u32, guide: &[EpisodeInfo]) -> Option<RuntimeMatch> {
+    if season.episodes.len() < 2 || season.episodes.iter().any(|episode| episode_hint(&episode.path).is_some()) {
+        return None
+    }
+
+    let durations = season.episodes.iter()
+        .map(|episode| media::inspect(&episode.path).ok().and_then(|info| info.duration))
+        .map(|duration| duration.map(|duration| duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9))
+        .collect::<Option<Vec<f64>>>()?;
+    let runtimes = (0..season.episodes.len() as u32)
+        .map(|index| provider::find(guide, season.season_no, episode_start.saturating_add(index)))
+        .map(|entry| entry.and_then(|entry| entry.runtime).map(|minutes| minutes as f64 * 60.0))
+        .collect::<Option<Vec<f64>>>()?;
+
+    Some(assign(&durations, &runtimes))
+}
+
+/// Assigns each duration to one of as many runtimes, so that the durations differ from their runtimes as little as
+/// possible in total.
+fn assign(durations: &[f64], runtimes: &[f64]) -> RuntimeMatch {
+    let costs = durations.iter()
+        .map(|duration| runtimes.iter().map(|runtime| (duration - runtime).abs()).collect())
+        .collect::<Vec<Vec<f64>>>();
+    let (positions, cost) = hungarian(&costs);
+
+    // Each file is only as certain as the assignment would be worse without it, and as its duration fits its runtime.
+    let confidence = positions.iter().enumerate()
+        .map(|(file, &position)| {
+            let mut forbidden = costs.clone();
+            forbidden[file][position] = FORBIDDEN;
+            let margin = (hungarian(&forbidden).1 - cost).max(0.0);
+            let tolerance = TOLERANCE.max(runtimes[position] / 10.0);
+            let fit = 1.0 - (costs[file][position] / tolerance).min(1.0);
+            fit * margin / (margin + SEPARATION)
+        })
+        .fold(1.0, f64::min);
+
+    RuntimeMatch { positions: positions, confidence: confidence }
+}
+
+/// A cost which no assignment would choose while another remains.
+const FORBIDDEN: f64 = 1e12;
+
+/// Solves the assignment problem of a square matrix of costs with the Hungarian algorithm, returning the column
+/// assigned to each row, and the total cost.
+fn hungarian(costs: &[Vec<f64>]) -> (Vec<usize>, f64) {
+    let n = costs.len();
+    // Potentials of the rows and columns, and the row matched to each column, counted from one, as the algorithm
+    // adds a column zero to start each augmenting path from.
+    let (mut u, mut v) = (vec![0.0; n + 1], vec![0.0; n + 1]);
+    let (mut matched, mut way) = (vec![0; n + 1], vec![0; n + 1]);
+    for row in 1..n + 1 {
+        matched[0] = row;
+        let mut column = 0;
+        let mut least = vec![::std::f64::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[column] = true;
+            let current = matched[column];
+            let (mut delta, mut next) = (::std::f64::INFINITY, 0);
+            for candidate in 1..n + 1 {
+                if used[candidate] { continue }
+                let reduced = costs[current - 1][candidate - 1] - u[current] - v[candidate];
+                if reduced < least[candidate] {
+                    least[candidate] = reduced;
+                    way[candidate] = column;
+                }
+                if least[candidate] < delta {
+                    delta = least[candidate];
+                    next = candidate;
+                }
+            }
+            for candidate in 0..n + 1 {
+                if used[candidate] {
+                    u[matched[candidate]] += delta;
+                    v[candidate] -= delta;
+                } else {
+                    least[candidate] -= delta;
+                }
+            }
+            column = next;
+            if matched[column] == 0 { break }
+        }
+
+        // Follow the augmenting path back to column zero.
+        loop {
+            let previous = way[column];
+            matched[column] = matched[previous];
+            column = previous;
+            if column == 0 { break }
+        }
+    }
+
+    let mut assignment = vec![0; n];
+    for column in 1..n + 1 {
+        assignment[matched[column] - 1] = column - 1;
+    }
+    let cost = assignment.iter().enumerate().map(|(row, &column)| costs[row][column]).sum();
+    (assignment, cost)
+}
+
+#[test]
+fn test_assign() {
+    // A double length premiere, a finale, and an episode between them, ripped in the wrong order.
+    let matched = assign(&[2580.0, 5340.0, 3050.0], &[88.0 * 60.0, 43.0 * 60.0, 51.0 * 60.0]);
+    assert_eq!(matched.positions, vec![1, 0, 2]);
+    assert!(matched.is_confident());
+
+    // Episodes of the same length could be in any order.
+    let matched = assign(&[2580.0, 2590.0, 2575.0], &[43.0 * 60.0, 43.0 * 60.0, 43.0 * 60.0]);
+    assert!(!matched.is_confident());
+
+    // Durations which fit no runtime are not trusted, however distinct.
+    let matched = assign(&[600.0, 1200.0], &[43.0 * 60.0, 88.0 * 60.0]);
+    assert!(!matched.is_confident());
+}
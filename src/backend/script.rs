@@ -0,0 +1,160 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::action::Action;
+use super::plan::{PlanError, RenamePlan};
+#[cfg(test)]
+use super::plan::Operation;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum ScriptError {
+        Plan(err: PlanError) {
+            from()
+            display("{}", err)
+        }
+        Displaced(path: PathBuf) {
+            display("{:?} would be moved to the Trash, which a script cannot do; choose another conflict policy", path)
+        }
+        Reflink {
+            display("reflinks cannot be made by a portable script, as only GNU cp can make them; choose another action")
+        }
+        NotUnicode(path: PathBuf) {
+            display("{:?} is not valid UTF-8, and cannot be written to a script", path)
+        }
+    }
+}
+
+/// The languages that a plan may be written out as.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScriptFormat {
+    /// A POSIX shell script.
+    Sh,
+}
+
+impl FromStr for ScriptFormat {
+    type Err = ();
+    fn from_str(format: &str) -> Result<ScriptFormat, ()> {
+        match format {
+            "sh" => Ok(ScriptFormat::Sh),
+            _    => Err(())
+        }
+    }
+}
+
+/// Generates a script which performs the plan without this program, for systems which cannot run it.
+pub fn generate(format: ScriptFormat, plan: &RenamePlan) -> Result<String, ScriptError> {
+    match format {
+        ScriptFormat::Sh => shell(plan)
+    }
+}
+
+/// Generates a POSIX shell script that carries out the plan in the order given by `RenamePlan::schedule`, and
+/// reverts it in the opposite order when it is given the `undo` argument. Existing files are never overwritten:
+/// the script stops at the first file whose new name is taken.
+fn shell(plan: &RenamePlan) -> Result<String, ScriptError> {
+    if let Some(operation) = plan.displaced.first() {
+        return Err(ScriptError::Displaced(operation.target.clone()));
+    }
+    if plan.action == Action::Reflink {
+        return Err(ScriptError::Reflink);
+    }
+
+    let steps = plan.schedule()?;
+    let mut apply = String::new();
+    let mut revert = String::new();
+    for step in &steps {
+        // Symbolic links are resolved relative to their own directory, so they point to an absolute path instead.
+        let source = match plan.action {
+            Action::Symlink => step.source.canonicalize().unwrap_or_else(|_| step.source.clone()),
+            _               => step.source.clone()
+        };
+        let (source, target) = (quote(&source)?, quote(&step.target)?);
+        if step.source.parent() != step.target.parent() {
+            if let Some(parent) = step.target.parent().filter(|parent| parent != &Path::new("")) {
+                apply.push_str(&format!("    mkdir -p -- {}\n", quote(parent)?));
+            }
+        }
+        apply.push_str(&format!("    place '{}' {} {}\n", command(plan.action), source, target));
+    }
+
+    for step in steps.iter().rev() {
+        let (source, target) = (quote(&step.source)?, quote(&step.target)?);
+        if plan.action.vacates_source() {
+            revert.push_str(&format!("    place 'mv -n' {} {}\n", target, source));
+        } else {
+            revert.push_str(&format!("    rm -f -- {}\n", target));
+        }
+    }
+
+    Ok(format!(r#"#!/bin/sh
+# Generated by tv-renamer to {action} the episodes of {series}.
+# Run `sh SCRIPT` to apply the plan, or `sh SCRIPT undo` to revert it.
+set -eu
+
+# Refuses to replace an existing file, as `mv -n` and `cp -n` silently skip the file instead of failing.
+place() {{
+    if [ -e "$3" ] || [ -L "$3" ]; then
+        printf 'tv-renamer: %s already exists\n' "$3" >&2
+        exit 1
+    fi
+    $1 -- "$2" "$3"
+}}
+
+apply() {{
+{apply}}}
+
+revert() {{
+{revert}}}
+
+case "${{1:-}}" in
+    "")   apply ;;
+    undo) revert ;;
+    *)    printf 'usage: %s [undo]\n' "$0" >&2; exit 2 ;;
+esac
+"#, series = plan.series_name.replace(|character: char| character.is_control(), " "),
+        action = plan.action.as_str(), apply = apply, revert = revert))
+}
+
+/// The shell command which performs the given action, which is given to `place`.
+fn command(action: Action) -> &'static str {
+    match action {
+        Action::Rename | Action::Move => "mv -n",
+        Action::Copy                  => "cp -n",
+        Action::Hardlink              => "ln",
+        Action::Symlink               => "ln -s",
+        Action::Reflink               => unreachable!("scripts are not generated for reflinks"),
+    }
+}
+
+/// Quotes a path for the shell, by wrapping it in single quotes and escaping any single quotes within.
+fn quote(path: &Path) -> Result<String, ScriptError> {
+    let path = path.to_str().ok_or_else(|| ScriptError::NotUnicode(path.to_path_buf()))?;
+    Ok(["'", &path.replace('\'', r"'\''"), "'"].concat())
+}
+
+#[test]
+fn test_quote() {
+    assert_eq!(quote(Path::new("/tv/It's Always Sunny/1x01.mkv")).unwrap(), r"'/tv/It'\''s Always Sunny/1x01.mkv'");
+    assert_eq!(quote(Path::new("$(rm -rf ~) `x`")).unwrap(), "'$(rm -rf ~) `x`'");
+}
+
+#[test]
+fn test_shell() {
+    let mut plan = RenamePlan::new(Action::Rename);
+    plan.series_name = String::from("Show");
+    plan.push(Operation {
+        source: PathBuf::from("/tv/a.mkv"), target: PathBuf::from("/tv/Show - 1x01.mkv"),
+        series_id: 1, season_no: 1, episode_no: 1
+    });
+
+    let script = shell(&plan).unwrap();
+    assert!(script.contains("apply() {\n    place 'mv -n' '/tv/a.mkv' '/tv/Show - 1x01.mkv'\n}"));
+    assert!(script.contains("revert() {\n    place 'mv -n' '/tv/Show - 1x01.mkv' '/tv/a.mkv'\n}"));
+
+    plan.action = Action::Reflink;
+    match shell(&plan) {
+        Err(ScriptError::Reflink) => (),
+        _ => panic!("expected reflinks to be refused")
+    }
+}
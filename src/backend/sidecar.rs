@@ -0,0 +1,89 @@
+use std::path::{Path, PathBuf};
+
+/// Extensions of companion files that are renamed along with the video that they belong to.
+const EXTENSIONS: &'static [&'static str] = &[
+    // Subtitles
+    "srt", "ass", "ssa", "sub", "idx", "vtt", "sup", "smi",
+    // Metadata
+    "nfo",
+    // Artwork
+    "jpg", "jpeg", "png", "tbn",
+];
+
+/// Returns true if the extension of the given path belongs to a known companion file.
+pub fn is_sidecar(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| EXTENSIONS.iter().any(|known| known.eq_ignore_ascii_case(extension)))
+        .unwrap_or(false)
+}
+
+/// Obtains the remainder of the sidecar's file name after the video's stem, such as `.en.forced.srt` or
+/// `-thumb.jpg`. Returns `None` if the sidecar does not belong to the video.
+fn suffix<'a>(video: &Path, sidecar: &'a Path) -> Option<&'a str> {
+    if video.parent() != sidecar.parent() { return None }
+    let stem = video.file_stem().and_then(|stem| stem.to_str())?;
+    let name = sidecar.file_name().and_then(|name| name.to_str())?;
+    if !name.starts_with(stem) { return None }
+    let remainder = &name[stem.len()..];
+    if remainder.starts_with('.') || remainder.starts_with('-') { Some(remainder) } else { None }
+}
+
+/// Assigns each candidate to the video that it accompanies, returning one list of sidecars per video.
+/// When more than one video's stem matches a candidate, the longest stem wins, so that `Show.Part2.srt`
+/// belongs to `Show.Part2.mkv` rather than `Show.mkv`. Candidates which match no video are discarded.
+pub fn associate(videos: &[PathBuf], candidates: Vec<PathBuf>) -> Vec<Vec<PathBuf>> {
+    let mut output: Vec<Vec<PathBuf>> = videos.iter().map(|_| Vec::new()).collect();
+    for candidate in candidates {
+        let owner = videos.iter().enumerate()
+            .filter_map(|(id, video)| suffix(video, &candidate).map(|suffix| (id, suffix.len())))
+            .min_by_key(|&(_, length)| length)
+            .map(|(id, _)| id);
+
+        if let Some(id) = owner {
+            output[id].push(candidate);
+        }
+    }
+
+    for sidecars in &mut output {
+        sidecars.sort();
+    }
+
+    output
+}
+
+/// Computes where a sidecar should be moved to when its video is renamed from `source` to `target`,
+/// preserving any language or flag suffixes that follow the video's stem.
+pub fn target(source: &Path, target: &Path, sidecar: &Path) -> Option<PathBuf> {
+    let suffix = suffix(source, sidecar)?;
+    let stem = target.file_stem().and_then(|stem| stem.to_str())?;
+    target.parent().map(|parent| parent.join([stem, suffix].concat()))
+}
+
+#[test]
+fn test_associate() {
+    let videos = vec![PathBuf::from("/tv/Show.mkv"), PathBuf::from("/tv/Show.Part2.mkv")];
+    let candidates = vec![
+        PathBuf::from("/tv/Show.en.forced.srt"),
+        PathBuf::from("/tv/Show.Part2.srt"),
+        PathBuf::from("/tv/Show-thumb.jpg"),
+        PathBuf::from("/tv/Showcase.nfo"),
+        PathBuf::from("/tv/other/Show.nfo"),
+    ];
+    let sidecars = associate(&videos, candidates);
+    assert_eq!(sidecars[0], vec![PathBuf::from("/tv/Show-thumb.jpg"), PathBuf::from("/tv/Show.en.forced.srt")]);
+    assert_eq!(sidecars[1], vec![PathBuf::from("/tv/Show.Part2.srt")]);
+}
+
+#[test]
+fn test_target() {
+    let source = Path::new("/tv/Episode.mkv");
+    let renamed = Path::new("/tv/Series - 1x01 - Title.mkv");
+    assert_eq!(target(source, renamed, Path::new("/tv/Episode.en.forced.srt")),
+        Some(PathBuf::from("/tv/Series - 1x01 - Title.en.forced.srt")));
+    assert_eq!(target(source, renamed, Path::new("/tv/Episode-thumb.jpg")),
+        Some(PathBuf::from("/tv/Series - 1x01 - Title-thumb.jpg")));
+    assert_eq!(target(source, renamed, Path::new("/tv/Episode.nfo")),
+        Some(PathBuf::from("/tv/Series - 1x01 - Title.nfo")));
+    assert_eq!(target(source, renamed, Path::new("/tv/Episode 2.nfo")), None);
+}
@@ -0,0 +1,134 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// The name of the files which list the episodes of a directory in the order they are numbered in, when episodes
+/// are sorted by `SortOrder::File`.
+pub const ORDER_FILE: &'static str = ".tvrenamerorder";
+
+/// Determines the order in which the episodes of a season are numbered.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortOrder {
+    /// By name, comparing runs of digits by their value, so that `Episode 2` precedes `Episode 10`.
+    Natural,
+    /// By the time each file was last modified, oldest first.
+    Modified,
+    /// By the time each file was created, oldest first.
+    Created,
+    /// In the order given by the `.tvrenamerorder` file of each directory. Files which it does not list follow in
+    /// natural order.
+    File,
+}
+
+impl SortOrder {
+    /// Describes the order, as shown in a preview.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            SortOrder::Natural  => "the natural order of their names",
+            SortOrder::Modified => "the order in which they were last modified",
+            SortOrder::Created  => "the order in which they were created",
+            SortOrder::File     => "the order listed by .tvrenamerorder",
+        }
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = ();
+    fn from_str(order: &str) -> Result<SortOrder, ()> {
+        match order {
+            "natural"  => Ok(SortOrder::Natural),
+            "modified" => Ok(SortOrder::Modified),
+            "created"  => Ok(SortOrder::Created),
+            "file"     => Ok(SortOrder::File),
+            _          => Err(())
+        }
+    }
+}
+
+/// Sorts the episodes found within a directory, whose order file is read if it is needed.
+pub fn sort(episodes: &mut Vec<PathBuf>, directory: &Path, order: SortOrder) -> io::Result<()> {
+    episodes.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+    match order {
+        SortOrder::Natural  => (),
+        SortOrder::Modified => sort_by_time(episodes, |metadata| metadata.modified()),
+        // File systems which do not record the creation time fall back to the modification time.
+        SortOrder::Created  => sort_by_time(episodes, |metadata| metadata.created().or_else(|_| metadata.modified())),
+        SortOrder::File     => {
+            let listed = match fs::read_to_string(directory.join(ORDER_FILE)) {
+                Ok(contents) => contents,
+                Err(ref why) if why.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(why) => return Err(why)
+            };
+
+            // Entries are paths relative to the directory, or the names of files, one per line.
+            let listed = listed.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>();
+            let position = |path: &Path| listed.iter().position(|&entry| {
+                path.strip_prefix(directory).map_or(false, |relative| relative == Path::new(entry))
+                    || path.file_name().map_or(false, |name| name == entry)
+            }).unwrap_or(listed.len());
+            episodes.sort_by_key(|path| position(path));
+        }
+    }
+    Ok(())
+}
+
+/// Sorts by a time of each file, keeping files with equal or unknown times in their existing order.
+fn sort_by_time<F>(episodes: &mut Vec<PathBuf>, time: F) where F: Fn(&fs::Metadata) -> io::Result<SystemTime> {
+    episodes.sort_by_key(|path| fs::metadata(path).and_then(|metadata| time(&metadata)).ok());
+}
+
+/// Compares two names, ignoring case, with runs of digits compared by their value.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().cloned(), b.peek().cloned()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = digits(&mut a);
+                let y = digits(&mut b);
+                // Leading zeros are ignored, so longer runs are larger numbers.
+                let (x_value, y_value) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ordering = x_value.len().cmp(&y_value.len()).then_with(|| x_value.cmp(y_value))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ordering != Ordering::Equal { return ordering }
+            },
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal { return ordering }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn digits<I: Iterator<Item = char>>(chars: &mut ::std::iter::Peekable<I>) -> String {
+    let mut digits = String::new();
+    while let Some(&character) = chars.peek() {
+        if !character.is_ascii_digit() { break }
+        digits.push(character);
+        chars.next();
+    }
+    digits
+}
+
+#[test]
+fn test_sort() {
+    use backend::testing::TempDir;
+
+    let mut names = vec!["Episode 10.mkv", "episode 2.mkv", "Episode 1.mkv", "Episode 02b.mkv", "Episode 02.mkv"];
+    names.sort_by(|a, b| natural_cmp(a, b));
+    assert_eq!(names, vec!["Episode 1.mkv", "episode 2.mkv", "Episode 02.mkv", "Episode 02b.mkv", "Episode 10.mkv"]);
+
+    let directory = TempDir::new("sort");
+    fs::create_dir_all(directory.join("Disc 2")).unwrap();
+    fs::write(directory.join(ORDER_FILE), "Disc 2/c.mkv\na.mkv\n").unwrap();
+    let mut episodes = vec![directory.join("a.mkv"), directory.join("b.mkv"), directory.join("Disc 2/c.mkv")];
+    sort(&mut episodes, &directory, SortOrder::File).unwrap();
+    assert_eq!(episodes, vec![directory.join("Disc 2/c.mkv"), directory.join("a.mkv"), directory.join("b.mkv")]);
+}
@@ -0,0 +1,35 @@
+use std::env;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static DIRECTORIES: AtomicUsize = AtomicUsize::new(0);
+
+/// A directory for the files of a test, which is removed along with its contents when it is dropped, even if the
+/// test panics. Each directory is unique to its test, as tests run in parallel within the same process.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let number = DIRECTORIES.fetch_add(1, Ordering::SeqCst);
+        let path = env::temp_dir().join(format!("tv-renamer-{}-{}-{}", name, process::id(), number));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+    fn deref(&self) -> &Path { &self.0 }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path { &self.0 }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) { let _ = fs::remove_dir_all(&self.0); }
+}
@@ -6,22 +6,25 @@ pub enum TemplateToken {
     Episode,
     TvdbTitle,
     TvdbFirstAired,
+    /// The properties of the video file itself, which are read from its container.
+    Resolution,
+    VideoCodec,
+    AudioCodec,
+    Channels,
+    Hdr,
+    Duration,
 
 }
 
-// The default template signature is `${SERIES} ${SEASON}x${EPISODE} ${TITLE}`
-pub fn default_template() -> Vec<TemplateToken> {
-    vec![TemplateToken::Series,
-         TemplateToken::Character(' '),
-         TemplateToken::Character('-'),
-         TemplateToken::Character(' '),
-         TemplateToken::Season,
-         TemplateToken::Character('x'),
-         TemplateToken::Episode,
-         TemplateToken::Character(' '),
-         TemplateToken::Character('-'),
-         TemplateToken::Character(' '),
-         TemplateToken::TvdbTitle]
+impl TemplateToken {
+    /// Whether the token is replaced by a property of the video file, which must be read to rename it.
+    pub fn is_media(&self) -> bool {
+        match *self {
+            TemplateToken::Resolution | TemplateToken::VideoCodec | TemplateToken::AudioCodec
+                | TemplateToken::Channels | TemplateToken::Hdr | TemplateToken::Duration => true,
+            _ => false
+        }
+    }
 }
 
 /// This tokenizer will take the template string as input and convert it into an ordered vector of tokens.
@@ -85,13 +88,23 @@ fn match_token(pattern: &str) -> Option<TemplateToken> {
         "${Episode}"          => Some(TemplateToken::Episode),
         "${TVDB_Title}"       => Some(TemplateToken::TvdbTitle),
         "${TVDB_First_Aired}" => Some(TemplateToken::TvdbFirstAired),
+        "${Resolution}"       => Some(TemplateToken::Resolution),
+        "${VideoCodec}"       => Some(TemplateToken::VideoCodec),
+        "${AudioCodec}"       => Some(TemplateToken::AudioCodec),
+        "${Channels}"         => Some(TemplateToken::Channels),
+        "${HDR}"              => Some(TemplateToken::Hdr),
+        "${Duration}"         => Some(TemplateToken::Duration),
         _                     => None
     }
 }
 
 #[test]
 fn test_tokenize() {
-    assert_eq!(default_template(), tokenize_template("${Series} - ${Season}x${Episode} - ${TVDB_Title}"));
+    use self::TemplateToken::*;
+    assert_eq!(tokenize_template("${Series} - ${Season}x${Episode} - ${TVDB_Title}"), vec![
+        Series, Character(' '), Character('-'), Character(' '), Season, Character('x'), Episode,
+        Character(' '), Character('-'), Character(' '), TvdbTitle
+    ]);
 }
 
 #[test]
@@ -101,5 +114,7 @@ fn test_match_token() {
     assert_eq!(Some(TemplateToken::Episode), match_token("${Episode}"));
     assert_eq!(Some(TemplateToken::TvdbTitle), match_token("${TVDB_Title}"));
     assert_eq!(Some(TemplateToken::TvdbFirstAired), match_token("${TVDB_First_Aired}"));
+    assert_eq!(Some(TemplateToken::Resolution), match_token("${Resolution}"));
+    assert_eq!(Some(TemplateToken::Hdr), match_token("${HDR}"));
     assert_eq!(None, match_token("${invalid}"));
 }
@@ -0,0 +1,133 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(unix)]
+use libc;
+
+use super::action;
+use super::date::civil_from_days;
+
+/// A file that was moved into the Trash, along with the information needed to restore it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Trashed {
+    /// Where the file was before it was trashed.
+    pub original: PathBuf,
+    /// Where the file now resides, within `Trash/files`.
+    pub file:     PathBuf,
+    /// The accompanying `.trashinfo` file, within `Trash/info`.
+    pub info:     PathBuf,
+}
+
+/// The home trash as defined by the freedesktop.org Trash specification: `$XDG_DATA_HOME/Trash`, which defaults
+/// to `~/.local/share/Trash`.
+pub fn home_trash() -> io::Result<PathBuf> {
+    env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::home_dir().map(|home| home.join(".local/share")))
+        .map(|data| data.join("Trash"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "unable to locate the home directory"))
+}
+
+/// Moves a file into the home trash, writing the `.trashinfo` file that allows file managers to restore it.
+pub fn trash(path: &Path) -> io::Result<Trashed> {
+    let original = if path.is_absolute() { path.to_path_buf() } else { env::current_dir()?.join(path) };
+    let trash = home_trash()?;
+    let files = trash.join("files");
+    let infos = trash.join("info");
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&infos)?;
+
+    let name = original.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cannot trash a path without a file name"))?
+        .to_string_lossy().into_owned();
+
+    // The info file is created exclusively first to reserve the name, as the specification requires.
+    let mut counter = 1;
+    loop {
+        let candidate = if counter == 1 { name.clone() } else { format!("{}.{}", name, counter) };
+        let info = infos.join([&candidate, ".trashinfo"].concat());
+        let file = files.join(&candidate);
+        counter += 1;
+        if file.exists() { continue }
+
+        let mut info_file = match OpenOptions::new().write(true).create_new(true).open(&info) {
+            Ok(info_file) => info_file,
+            Err(ref why) if why.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(why) => return Err(why)
+        };
+
+        write!(info_file, "[Trash Info]\nPath={}\nDeletionDate={}\n", encode(&original), deletion_date())?;
+        if let Err(why) = fs::rename(&original, &file) {
+            let _ = fs::remove_file(&info);
+            // Only the home trash is supported, which files on other file systems cannot be moved into.
+            if action::is_cross_device(&why) {
+                return Err(io::Error::new(io::ErrorKind::Other, format!("{:?} is not on the same file system as the \
+                    Trash in {:?}, which it cannot be moved into; choose another conflict policy", original, trash)));
+            }
+            return Err(why);
+        }
+
+        return Ok(Trashed { original: original, file: file, info: info });
+    }
+}
+
+/// Moves a trashed file back to where it came from, and removes its `.trashinfo` file.
+pub fn restore(trashed: &Trashed) -> io::Result<()> {
+    fs::rename(&trashed.file, &trashed.original)?;
+    fs::remove_file(&trashed.info)
+}
+
+/// Percent-encodes a path for the `Path` key, leaving the characters that are unreserved in URIs intact.
+fn encode(path: &Path) -> String {
+    let mut output = String::new();
+    for &byte in path.to_string_lossy().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => output.push(byte as char),
+            _ => output.push_str(&format!("%{:02X}", byte))
+        }
+    }
+    output
+}
+
+/// The current time in the `YYYY-MM-DDThh:mm:ss` format used by the `DeletionDate` key, which the specification
+/// requires to be in local time. UTC is used where the local time zone is unknown.
+fn deletion_date() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+    let (year, month, day, time) = local_time(seconds).unwrap_or_else(|| {
+        let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+        (year, month, day, seconds % 86400)
+    });
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, time / 3600, time % 3600 / 60, time % 60)
+}
+
+/// Converts seconds since the UNIX epoch into the local date, along with the seconds since local midnight.
+#[cfg(unix)]
+fn local_time(seconds: u64) -> Option<(i64, u32, u32, u64)> {
+    use std::mem;
+
+    let time = seconds as libc::time_t;
+    let mut local: libc::tm = unsafe { mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut local) }.is_null() { return None }
+    let time = local.tm_hour as u64 * 3600 + local.tm_min as u64 * 60 + local.tm_sec as u64;
+    Some((local.tm_year as i64 + 1900, local.tm_mon as u32 + 1, local.tm_mday as u32, time))
+}
+
+#[cfg(not(unix))]
+fn local_time(_seconds: u64) -> Option<(i64, u32, u32, u64)> { None }
+
+#[test]
+fn test_encode() {
+    assert_eq!(encode(Path::new("/tv/Show - 1x01.mkv")), "/tv/Show%20-%201x01.mkv");
+}
+
+#[test]
+fn test_deletion_date() {
+    // Local time differs from UTC by less than a day, whatever the time zone.
+    let date = deletion_date();
+    assert_eq!((date.len(), &date[10..11], &date[13..14]), (19, "T", ":"));
+    let (year, month, day, time) = local_time(86400 * 11016 + 43200).unwrap_or((2000, 2, 29, 43200));
+    assert!(year == 2000 && [(2, 28), (2, 29), (3, 1)].contains(&(month, day)) && time < 86400);
+}
@@ -1,49 +1,106 @@
-pub const MAN_PAGE: &'static str = r#"NAME:
-    tv-renamer - rename TV series and movies
-
-SYNOPSIS:
-    tv-renamer DIRECTORY [-d | --dry-run]
-                         [-n | --series-name "NAME OF SERIES"]
-                         [-s | --season-number NUMBER]
-                         [-t | --template "TEMPLATE"]
-                         [-p | --pad-length NUMBER]
-                         [-e | --episode-start NUMBER]
-
-DESCRIPTION:
+pub const MAN_PAGE: &'static str = r#"DESCRIPTION:
     Renames all videos in a directory according to their season and episode.
 
     If the given DIRECTORY contains season directories, it will automatically rename episodes in each season.
 
     If no DIRECTORY is given, the default path will be the current working directory.
 
-    It is recommended to use the dry-run option first before committing any changes.
+    It is recommended to use the preview subcommand first before committing any changes.
 
-    If a target file already exists, the command will ask if it is okay to overwrite the file.
+    If a target file already exists, the command will ask what to do with it, unless a different --on-conflict
+    policy is given.
 
-    Please ensure that all of the files in the directory are video files that you want renamed.
-
-OPTIONS:
-    -d, --dry-run:
-        Runs through all of the files and prints what would happen without doing anything.
-
-    -n, --series-name:
-        Sets the name of the series to be renamed. [not optional]
-
-    -s, --season-number:
-        Sets the season number to use when renaming a file. [default: 1]
+    Files are only renamed once every new name has been determined, and all renames are carried out as a single
+    transaction: if any rename fails, the renames that were already made are reverted in reverse order. Renames that
+    depend on one another, such as two files swapping names, are ordered so that no file is overwritten before it
+    has been renamed, and the run stops if two files would be given the same name.
 
-    -t, --template:
-        Sets the template that will define the naming scheme.
-        [default: "${Series} - ${Season}x${Episode} - ${TVDB_Title}"]
-
-    -e, --episode-start:
-        Sets the episode number to start counting from. [default: 1]
-
-    -p, --pad-length:
-        Sets the number of digits to pad the episode count for. [default: 2]
+    Please ensure that all of the files in the directory are video files that you want renamed.
 
-    -v, --verbose:
-        Print the changes that are occurring.
+    Subtitles, NFO files and artwork that share a video's file name, such as "Episode.en.forced.srt" or
+    "Episode-thumb.jpg", are renamed along with the video, keeping their language and flag suffixes.
+
+    If a directory without season directories holds episodes of several seasons, as given by their file names such
+    as S02E05, its episodes are grouped by those seasons and numbered by the episode numbers in their names. A
+    season which also holds files whose names give no number is only numbered in order with --force.
+
+    Episodes are also found within the subdirectories of a season, such as "Season 1/Disc 2", down to the depth
+    given by --max-depth. Hidden directories and directories named Sample, Extras or Featurettes are left out, along
+    with any path listed by a .tvrenamerignore file, which uses the syntax of .gitignore and applies to the directory
+    it is in and every directory below it.
+
+    Before anything is renamed, the episodes of each season are compared with the episode guide. Files beyond the
+    last episode, episodes claimed by several files, files whose names give another episode number than the one
+    they would be renamed to, and specials among regular episodes stop the run unless --force is given. Missing
+    episodes are only reported.
+
+    With --library, each directory within DIRECTORY is treated as a series named after it, and every series is
+    renamed in a single run, which ends with a summary of each series. A series which fails does not stop the
+    others from being renamed.
+
+    Each subcommand describes its own options with `tv-renamer help SUBCOMMAND`.
+
+CONFIGURATION:
+    The defaults of the options of `rename`, `preview` and `episodes`, along with the language of episode titles and
+    the TVDB API key, are read from $XDG_CONFIG_HOME/tv-renamer/config.toml (~/.config/tv-renamer/config.toml by
+    default). The settings of the [default] table always apply, and those of a profile apply on top of them when it
+    is selected with --profile. Options given on the command line override both.
+
+        [default]
+        pad-length = 2
+        language = "eng"
+
+        [profiles.anime]
+        template = "${Series} - ${Episode} - ${TVDB_Title}"
+        pad-length = 3
+        order = "absolute"
+
+        [profiles.plex]
+        template = "${Series} - S${Season}E${Episode} - ${TVDB_Title}"
+        target-directory = "/srv/media/tv"
+        action = "move"
+
+    The keys are template, pad-length, episode-start, on-conflict, action, target-directory, max-depth,
+    season-patterns, sort, include-extensions, exclude-extensions, junk, junk-durations, match-runtime, order,
+    language, api-key and pin.
+
+    Season directories are recognized by their own names, ignoring case, such as "Season 1", "S01", "Series 2",
+    "Staffel 3", "Saison 4", "第1季" or "Season 2019", and "Specials" is season 0. Seasons numbered by year are
+    named with the year by ${Season}. season-patterns lists regular expressions which are tried before these, whose
+    first capture group is the number of the season, such as '^Book ([0-9]+)'.
+
+    Videos are recognized by their extensions, ignoring case: those which are built in, such as mkv, mp4 and avi,
+    and those of video types in the mime/globs2 file of the shared MIME database, when it is installed.
+    include-extensions adds extensions, such as ['strm'], and exclude-extensions removes them. Files without an
+    extension are recognized by their contents, and are given the extension of their container when renamed.
+
+    TVDB is accessed with the API key given by api-key or $TVDB_API_KEY, along with the subscriber PIN given by pin
+    or $TVDB_PIN if the key requires one. There is no built-in key, so one must be registered with TVDB before series
+    can be looked up. The environment takes precedence over the configuration file. The token which TVDB issues for
+    them is kept under $XDG_CACHE_HOME/tv-renamer, and is replaced when it expires.
+
+APPLY:
+    `tv-renamer apply PLAN` carries out a plan written by `rename --plan-out`, which may have been edited since.
+    Nothing is renamed if any file in the plan no longer exists. Conflicts with files that have appeared since the
+    plan was written are resolved with the --on-conflict policy, which defaults to abort.
+
+UNDO:
+    Every batch of renames is recorded in a journal under $XDG_STATE_HOME/tv-renamer (~/.local/state/tv-renamer by
+    default), along with the time of the rename and the TVDB IDs that produced each name.
+
+    `tv-renamer undo` reverts the most recent batch, and `tv-renamer undo BATCH-ID` reverts a specific batch. Nothing
+    is reverted if any renamed file has been moved or modified since, or if its original name has been taken.
+    Files which the batch moved into the Trash are restored, and nothing is reverted if any has left the Trash since.
+    Files which it overwrote cannot be restored, and are listed instead.
+
+EXIT STATUS:
+    0   Success.
+    1   Invalid arguments, or any other failure.
+    2   The series could not be found on TVDB. (kind: series_not_found)
+    3   An episode could not be found within the series on TVDB. (kind: episode_missing)
+    4   A file would be renamed over an existing file or another renamed file. (kind: conflict)
+    5   A file or directory could not be read or written. (kind: io)
+    6   The episodes of a directory do not match the episode guide. (kind: check)
 
 EXAMPLE:
     When executed inside of a directory with the name of the TV Series
@@ -55,7 +112,7 @@ EXAMPLE:
 
     You can define your own naming scheme with --template:
         > one.mkv two.mkv three.mkv
-        > tv-renamer -t "${Series} S${Season}E${Episode} - ${TVDB_Title}"
+        > tv-renamer rename -t "${Series} S${Season}E${Episode} - ${TVDB_Title}"
         > "TV Series S1E01 - Episode Title.mkv"
         > "TV Series S1E02 - Episode Title.mkv"
         > "TV Series S1E03 - Episode Title.mkv"
@@ -63,10 +120,115 @@ EXAMPLE:
     The season name can also be automatically inferred:
         > "TV Series/Season1"
         > "TV Series/Season2"
-        > tv-renamer "TV Series"
+        > tv-renamer preview "TV Series"
         > "TV Series/Season1/TV Series - 1x01 - Episode Title.mkv"
         > "TV Series/Season2/TV Series - 2x01 - Episode Title.mkv"
 
 AUTHOR:
     Written by Michael Aaron Murphy.
 "#;
+
+pub const TEMPLATE_HELP: &'static str = r#"Sets the template that will define the naming scheme.
+[default: "${Series} - ${Season}x${Episode} - ${TVDB_Title}"]
+    ${Series}:           name of the TV series
+    ${Season}:           season of the TV series
+    ${Episode}:          current episode number
+    ${TVDB_Title}:       title of the current episode from TVDB
+    ${TVDB_First_Aired}: the first airing date of the episode from TVDB
+    ${Resolution}:       resolution of the video, such as 720p, 1080p or 2160p
+    ${VideoCodec}:       codec of the video, such as x264, x265, AV1 or VP9
+    ${AudioCodec}:       codec of the first audio track, such as AAC, AC3, EAC3, DTS or TrueHD
+    ${Channels}:         channel layout of the first audio track, such as 2.0 or 5.1
+    ${HDR}:              DV, HDR10 or HLG when the video has a high dynamic range
+    ${Duration}:         duration of the video in minutes, such as 42m
+The last six are read from the headers of Matroska, WebM and MP4 files, and are empty when the file does not give
+them, such as for other containers."#;
+
+pub const CONFLICT_HELP: &'static str = r#"Sets what to do when a file with the new name already exists.
+    skip:      leave the episode and its companion files with their current names
+    overwrite: replace the existing file, with any action, which is restored if a rename fails
+    suffix:    keep both files by adding a numbered suffix, such as " (2)", to the new name
+    trash:     move the existing file into the Trash (~/.local/share/Trash) before renaming
+    ask:       ask which of the above to do for each conflict
+    abort:     stop without renaming anything"#;
+
+pub const ACTION_HELP: &'static str = r#"Sets how each file is placed at its new name.
+    rename:   rename the file, which fails if the target directory is on another file system
+    move:     rename the file, or copy, verify and delete it if the target is on another file system
+    copy:     copy the file and verify the copy, leaving the original untouched
+    hardlink: create a hard link to the original
+    symlink:  create a symbolic link to the original
+    reflink:  create a copy-on-write clone of the original, on file systems such as Btrfs and XFS
+The space required by copies is checked before anything is changed."#;
+
+pub const SCRIPT_HELP: &'static str = r#"Prints a POSIX shell script which carries out the plan, instead of renaming
+anything, so that the renames may be reviewed and run on systems which cannot run tv-renamer. The script never
+overwrites a file, and running it with the `undo` argument reverts it. Files cannot be moved to the Trash by a
+script, so the trash conflict policy cannot be used, and only GNU cp can make reflinks, so the reflink action cannot
+be used either."#;
+
+pub const FORMAT_HELP: &'static str = r#"Sets the format of the output.
+    text: colored paths when previewing or when --verbose is given, and messages on standard error
+    json: one JSON object per line on standard output, whose "event" field is one of:
+        planned:  a file will be placed at a new name, or moved to the Trash if its action is "trash"
+        skipped:  a file will keep its name, as its new name is taken
+        matched:  the files of a season were compared with the runtimes of its episodes, with the given "confidence",
+                  and numbered by them if "applied" is true
+        excluded: a video will not be renamed, as its "reason" is that it is a "sample", a "trailer", "bonus"
+                  material, or too "small" or "short" to be an episode
+        renamed:  a file was placed at its new name by the batch with the given ID
+        reverted: a batch was undone, with the number of files that it "restored" from the Trash, and that it
+                  "overwritten" and could not be restored
+        check:    the episodes of a season do not match the episode guide, as listed by its "extra", "missing",
+                  "duplicates", "misnumbered", "specials" and "junk" fields
+        series:   a series was found by `search`
+        episode:  an episode of the guide printed by `episodes`
+        summary:  the outcome of a series of a library, whose "status" is "renamed", "previewed", "resumed" or
+                  "failed"
+        error:    the run failed, for the reason given by its "kind" and "message" fields"#;
+
+pub const ORDER_HELP: &'static str = r#"Sets the ordering of the episodes, as TVDB may number a series in several ways.
+    aired:     the order in which the episodes first aired
+    dvd:       the order of the episodes on DVD or Blu-ray
+    absolute:  every episode of the series numbered in order, as is common for anime
+    alternate: an alternate order, such as a director's cut
+    regional:  the order in which the episodes aired in another region"#;
+
+pub const PROFILE_HELP: &'static str = r#"Selects a profile of $XDG_CONFIG_HOME/tv-renamer/config.toml, such as
+[profiles.anime], whose settings become the defaults of the other options. The settings of the [default] table apply
+whether or not a profile is selected."#;
+
+pub const FORCE_HELP: &'static str = r#"Renames the episodes even if they do not match the episode guide. Files beyond
+the last episode of a season keep their names."#;
+
+pub const LIBRARY_HELP: &'static str = r#"Treats each directory within DIRECTORY as a series named after it, and renames
+every series in a single run, ending with a summary of each series. Every series is renamed as a batch of its own,
+which may be undone on its own. A series which fails, such as one which cannot be found on TVDB, is reported in the
+summary without stopping the others. --only limits the run to the given series, and --progress records each renamed
+series in a file, so that an interrupted run over a large library resumes where it stopped."#;
+
+pub const MATCH_RUNTIME_HELP: &'static str = r#"Numbers the episodes of seasons whose file names give no episode
+numbers, such as the VTS_01_1.mkv titles of DVD rips, by comparing the durations of the files with the runtimes of the
+episode guide, instead of by the order in which they were sorted. The durations are read from Matroska, WebM and MP4
+files. Each season is numbered by runtime only when every duration and runtime is known and the assignment is at
+least 50% confident, which it is not when episodes have similar runtimes, or when durations are far from every
+runtime. Previews state the confidence of each season."#;
+
+pub const JUNK_HELP: &'static str = r#"Sets what becomes of videos which do not appear to be episodes: those named as
+samples, trailers, teasers, promos, featurettes or bloopers, and those smaller than a fifth of the median size of the
+videos of their season, when it has at least three.
+    exclude: leaves them out of the numbering, and lists them in previews
+    flag:    numbers them along with the episodes, and reports them as problems, so that nothing is renamed without
+             --force"#;
+
+pub const JUNK_DURATIONS_HELP: &'static str = r#"Also takes videos which are shorter than a third of the median
+duration of the videos of their season as junk, when it has at least three whose durations are known. The durations
+are read from Matroska, WebM and MP4 files, which requires opening every video of the season."#;
+
+pub const SORT_HELP: &'static str = r#"Sets the order in which the episodes of a season are numbered, as stated by
+previews.
+    natural:  by name, comparing numbers by their value, so that "Episode 2" precedes "Episode 10"
+    modified: by the time each file was last modified, oldest first
+    created:  by the time each file was created, oldest first
+    file:     in the order listed by the .tvrenamerorder file of the season directory, one file per line, followed by
+              the files it does not list in natural order"#;
@@ -1,217 +1,816 @@
 mod man;
-use backend::{self, Arguments, Season, ScanDir, TargetErr};
-use backend::tokenizer;
-use self::man::MAN_PAGE;
+mod output;
+use backend::{self, Arguments, Season, ScanDir, ScanOptions, TargetErr};
+use backend::{preflight, runtime, sidecar, tokenizer};
+use backend::export;
+use backend::journal::{self, Batch};
+use backend::junk::JunkPolicy;
+use backend::progress::Progress;
+use backend::report::{self, ReportFormat, SeriesReport};
+use backend::config::{self, Settings};
+use backend::conflict::ConflictPolicy;
+use backend::plan::{Operation, RenamePlan};
+use backend::provider::{self, EpisodeInfo, Provider};
+use backend::script::{self, ScriptFormat};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use frontend::gtk3;
+use self::man::{ACTION_HELP, CONFLICT_HELP, FORCE_HELP, FORMAT_HELP, LIBRARY_HELP, MAN_PAGE, ORDER_HELP, PROFILE_HELP};
+use self::man::{JUNK_DURATIONS_HELP, JUNK_HELP, MATCH_RUNTIME_HELP, SCRIPT_HELP, SORT_HELP, TEMPLATE_HELP};
+use self::output::{emit, fail, ErrorKind, Event, Failure};
 use std::env;
-use std::io::{self, Write, Read};
 use std::fs;
-use std::path::Path;
-use std::process;
-use tvdb;
-use backend::{DRY_RUN, VERBOSE};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use backend::{DRY_RUN, FORCE, JSON, VERBOSE};
 
-const EP_NO_VAL: &'static str = "no value was set for the episode count.\n";
-const SR_NO_VAL: &'static str = "no value was set for the series name.\n";
-const SN_NO_VAL: &'static str = "no value was set for the season number.\n";
-const PD_NO_VAL: &'static str = "no value was set for the pad length.\n";
-const TMP_NO_VAL:&'static str = "no value was set for the template.\n";
+/// Every subcommand, which are otherwise preceded by an implied `rename`.
+const SUBCOMMANDS: [&'static str; 9] = [
+    "rename", "preview", "apply", "undo", "search", "episodes", "report", "gtk", "help"
+];
 
 pub fn interface<A: Iterator<Item = String>>(args: A) {
-    let stderr = &mut io::stderr();
-
-    // Default CLI arguments
-    let mut arguments = Arguments {
-        flags:          0,
-        season_index:   1,
-        episode_index:  1,
-        pad_length:     2,
-        base_directory: String::with_capacity(256),
-        series_name:    String::with_capacity(64),
-        template:       tokenizer::default_template(),
+    let mut args = args.collect::<Vec<String>>();
+
+    // The global --profile option may precede the subcommand.
+    let mut index = 1;
+    while args.get(index).map_or(false, |argument| argument == "--profile" || argument.starts_with("--profile=")) {
+        index += if args[index] == "--profile" { 2 } else { 1 };
+    }
+
+    // When the last --profile lacks its value, the subcommand is implied before it, so that clap reports the missing
+    // value rather than taking the subcommand as the profile.
+    let index = if index > args.len() { args.len() - 1 } else { index };
+
+    // Renaming was all that the program did before it had subcommands, so a missing subcommand implies `rename`.
+    let implied = args.get(index).map_or(true, |argument| {
+        !SUBCOMMANDS.contains(&argument.as_str()) && !["-h", "--help", "-V", "--version"].contains(&argument.as_str())
+    });
+    if implied { args.insert(index, String::from("rename")); }
+
+    let matches = app().get_matches_from(args);
+    let (command, matches) = match matches.subcommand() {
+        (command, Some(matches)) => (command, matches),
+        _                        => unreachable!()
     };
 
-    // Attempt to parse the input arguments and act upon any errors that are returned
-    if let Err(why) = parse_arguments(&mut arguments, args) {
-        let _ = stderr.write(b"tv-renamer: ");
-        match why {
-            ParseError::NoEpisodeIndex           => { let _ = stderr.write(EP_NO_VAL.as_bytes()); },
-            ParseError::NoSeriesIndex            => { let _ = stderr.write(SR_NO_VAL.as_bytes()); },
-            ParseError::NoSeriesName             => { let _ = stderr.write(SN_NO_VAL.as_bytes()); },
-            ParseError::NoTemplate               => { let _ = stderr.write(TMP_NO_VAL.as_bytes()); },
-            ParseError::NoPadLength              => { let _ = stderr.write(PD_NO_VAL.as_bytes()); },
-            ParseError::EpisodeIndexIsNaN(value) => { let _ = write!(stderr, "episode index, `{}`, is not a number\n", value); },
-            ParseError::SeriesIndexIsNaN(value)  => { let _ = write!(stderr, "series index, `{}`, is not a number\n", value); },
-            ParseError::PadLengthIsNaN(value)    => { let _ = write!(stderr, "pad length, `{}`, is not a number\n", value); },
-            ParseError::InvalidArgument(value)   => { let _ = write!(stderr, "invalid argument: `{}`\n", value); },
-            ParseError::TooManyArguments(value)  => { let _ = write!(stderr, "too many arguments: `{}`\n", value); }
-            ParseError::NoCWD                    => { let _ = stderr.write(b"unable to get current working directory\n"); },
-            ParseError::CWDNotValid              => { let _ = stderr.write(b"current working directory is not valid UTF-8\n"); }
-        }
-        process::exit(1);
-    }
-
-    // Collect a list of episodes within a directory and rename them.
-    match backend::scan_directory(&arguments.base_directory, arguments.season_index) {
-        // If the directory contains episodes, rename the episodes.
-        Ok(ScanDir::Episodes(season)) => rename_season(stderr, &season, &arguments, arguments.episode_index),
-        // If the directory contains seasons, rename the episodes in each season.
-        Ok(ScanDir::Seasons(seasons)) => for season in seasons { rename_season(stderr, &season, &arguments, 1); },
-        // If an error occurred, print an error and exit.
-        Err(why) => {
-            let _ = writeln!(stderr, "tv-renamer: {}", why);
-            process::exit(1);
-        }
-    }
-}
-
-/// Renames all of the episodes in given season
-fn rename_season(stderr: &mut io::Stderr, season: &Season, arguments: &Arguments, episode_no: u16) {
-    let stdout = &mut io::stdout();
-    let mut episode_no = episode_no;
-
-    // TVDB
-    // https://github.com/thetvdb/v4-api
-    let api = tvdb::Tvdb::new("0629B785CE550C8D");
-    let series_id = match api.search(Some(&arguments.series_name), Some("en")) {
-        Ok(result) => result.data.unwrap()[0].id.unwrap(),
-        Err(err)     => {
-            let _ = write!(stderr, "tv-renamer: invalid TV series: {} err={}\n", &arguments.series_name, err);
-            process::exit(1);
-        }
+    // Options which are not given on the command line are taken from the selected profile of the configuration.
+    let settings = config::load(matches.value_of("profile"))
+        .unwrap_or_else(|why| fail(flags(matches), ErrorKind::Other, why));
+
+    match command {
+        "rename"   if matches.is_present("library") => library(matches, &settings, 0),
+        "preview"  if matches.is_present("library") => library(matches, &settings, DRY_RUN),
+        "rename"   => rename(arguments(matches, &settings, 0), &settings),
+        "preview"  => rename(arguments(matches, &settings, DRY_RUN), &settings),
+        "apply"    => apply(matches),
+        "undo"     => undo(matches),
+        "search"   => search(matches, &settings),
+        "episodes" => episodes(matches, &settings),
+        "report"   => report(matches, &settings),
+        "gtk"      => gtk3::interface(settings),
+        _          => unreachable!()
+    }
+}
+
+/// Defines every subcommand and its arguments.
+fn app() -> App<'static, 'static> {
+    App::new("tv-renamer")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author("Michael Aaron Murphy <mmstickman@gmail.com>")
+        .about("Renames TV series episodes with titles from TVDB")
+        .after_help(MAN_PAGE)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(Arg::with_name("profile").long("profile").takes_value(true).global(true)
+            .help("Selects a profile of the configuration file, which sets the default of other options")
+            .long_help(PROFILE_HELP))
+        .subcommand(rename_arguments(SubCommand::with_name("rename")
+            .about("Renames the episodes in a directory, which is the default when no subcommand is given")))
+        .subcommand(rename_arguments(SubCommand::with_name("preview")
+            .about("Prints how the episodes in a directory would be renamed, without renaming them")))
+        .subcommand(SubCommand::with_name("apply")
+            .about("Carries out a plan written by `rename --plan-out`, which may have been edited since")
+            .long_about("Carries out a plan written by `rename --plan-out`, which may have been edited since. \
+                Nothing is renamed if any file in the plan no longer exists. Conflicts with files that have \
+                appeared since the plan was written are resolved with the --on-conflict policy.")
+            .arg(Arg::with_name("PLAN").required(true).help("The plan to carry out, as JSON or CSV"))
+            .arg(dry_run())
+            .arg(verbose())
+            .arg(on_conflict().help("Sets what to do when a file with the new name already exists [default: abort]"))
+            .arg(format()))
+        .subcommand(SubCommand::with_name("undo")
+            .about("Reverts a batch of renames")
+            .long_about("Reverts a batch of renames, which are journaled under $XDG_STATE_HOME/tv-renamer. \
+                Nothing is reverted if any renamed file has been moved or modified since, or if its original \
+                name has been taken.")
+            .arg(Arg::with_name("BATCH-ID").help("The batch to revert [default: the most recent batch]"))
+            .arg(format()))
+        .subcommand(SubCommand::with_name("search")
+            .about("Searches TVDB for series with the given name")
+            .arg(Arg::with_name("NAME").required(true).help("The name of the series"))
+            .arg(format()))
+        .subcommand(SubCommand::with_name("episodes")
+            .about("Prints the episode guide of a series")
+            .long_about("Prints the season, episode and absolute number, air date and title of every episode of a \
+                series on TVDB, in the given ordering. Episode guides are cached under $XDG_CACHE_HOME/tv-renamer \
+                for a day, and are shared with `rename`.")
+            .arg(Arg::with_name("SERIES").required(true).help("The name of the series, or its TVDB ID with --id"))
+            .arg(Arg::with_name("id").short("i").long("id").help("Treats SERIES as a TVDB ID, as found by `search`"))
+            .arg(order())
+            .arg(Arg::with_name("season").short("s").long("season").takes_value(true)
+                .help("Only prints the episodes of the given season"))
+            .arg(Arg::with_name("refresh").long("refresh")
+                .help("Requests the episode guide again, instead of using the cached copy"))
+            .arg(format()))
+        .subcommand(SubCommand::with_name("report")
+            .about("Reports the missing, unaired and extra episodes of every series in a library")
+            .long_about("Reports the missing, unaired and extra episodes of every series in a library, whose \
+                directories are each named after a series and hold its episodes or season directories. Episodes are \
+                identified by the season and episode numbers in their file names, such as S01E05, or otherwise by \
+                their position, and compared with the episode guide on TVDB. Specials are only reported for series \
+                which have a specials directory.")
+            .arg(Arg::with_name("LIBRARY")
+                .help("The directory containing a directory for each series [default: the current directory]"))
+            .arg(order())
+            .arg(max_depth())
+            .arg(sort())
+            .arg(junk())
+            .arg(junk_durations())
+            .arg(Arg::with_name("refresh").long("refresh")
+                .help("Requests the episode guides again, instead of using the cached copies"))
+            .arg(Arg::with_name("output").short("o").long("output").value_name("FILE").takes_value(true)
+                .help("Writes the report to the given file instead of standard output"))
+            .arg(Arg::with_name("format").short("f").long("format").takes_value(true).default_value("markdown")
+                .possible_values(&["markdown", "html", "json"])
+                .help("Sets the format of the report")))
+        .subcommand(SubCommand::with_name("gtk")
+            .about("Opens the GTK3 interface"))
+}
+
+/// The arguments shared by `rename` and `preview`.
+fn rename_arguments(command: App<'static, 'static>) -> App<'static, 'static> {
+    command
+        .arg(Arg::with_name("DIRECTORY")
+            .help("The directory containing the episodes or season directories [default: the current directory]"))
+        .arg(dry_run())
+        .arg(verbose())
+        .arg(Arg::with_name("series-name").short("n").long("series-name").takes_value(true)
+            .help("Sets the name of the series [default: the name of the directory]"))
+        .arg(Arg::with_name("library").short("l").long("library")
+            .conflicts_with_all(&["series-name", "plan-out", "emit-script"])
+            .help("Treats each directory within DIRECTORY as a series named after it, and renames every series")
+            .long_help(LIBRARY_HELP))
+        .arg(Arg::with_name("only").long("only").value_name("SERIES").takes_value(true).multiple(true)
+            .number_of_values(1).requires("library")
+            .help("Only renames the series of the library with the given directory name, and may be repeated"))
+        .arg(Arg::with_name("progress").long("progress").value_name("FILE").takes_value(true).requires("library")
+            .help("Records each renamed series of the library in the given file, and skips those already recorded"))
+        .arg(Arg::with_name("season-number").short("s").long("season-number").takes_value(true).default_value("1")
+            .help("Sets the season number to use when renaming a file"))
+        .arg(Arg::with_name("template").short("t").long("template").takes_value(true)
+            .help("Sets the template that will define the naming scheme")
+            .long_help(TEMPLATE_HELP))
+        .arg(Arg::with_name("episode-start").short("e").long("episode-start").takes_value(true)
+            .help("Sets the episode number to start counting from [default: 1]"))
+        .arg(Arg::with_name("pad-length").short("p").long("pad-length").takes_value(true)
+            .help("Sets the number of digits to pad the episode count for [default: 2]"))
+        .arg(on_conflict().help("Sets what to do when a file with the new name already exists [default: ask]"))
+        .arg(Arg::with_name("force").long("force")
+            .help("Renames the episodes even if they do not match the episode guide")
+            .long_help(FORCE_HELP))
+        .arg(Arg::with_name("match-runtime").long("match-runtime")
+            .help("Numbers episodes whose names give no episode numbers by their durations and the guide's runtimes")
+            .long_help(MATCH_RUNTIME_HELP))
+        .arg(Arg::with_name("action").short("a").long("action").takes_value(true)
+            .possible_values(&["rename", "move", "copy", "hardlink", "symlink", "reflink"])
+            .help("Sets how each file is placed at its new name [default: rename]")
+            .long_help(ACTION_HELP))
+        .arg(order())
+        .arg(max_depth())
+        .arg(sort())
+        .arg(junk())
+        .arg(junk_durations())
+        .arg(Arg::with_name("target-directory").short("o").long("target-directory").takes_value(true)
+            .help("Places the renamed files under the given directory, keeping their season directories"))
+        .arg(Arg::with_name("plan-out").long("plan-out").value_name("PLAN").takes_value(true)
+            .help("Writes the plan to the given file before renaming, as CSV if it ends with .csv, or else JSON"))
+        .arg(Arg::with_name("emit-script").long("emit-script").takes_value(true).possible_values(&["sh"])
+            .help("Prints a script which carries out the plan, instead of renaming anything")
+            .long_help(SCRIPT_HELP))
+        .arg(format())
+}
+
+fn dry_run() -> Arg<'static, 'static> {
+    Arg::with_name("dry-run").short("d").long("dry-run")
+        .help("Prints what would happen without doing anything")
+}
+
+fn verbose() -> Arg<'static, 'static> {
+    Arg::with_name("verbose").short("v").long("verbose")
+        .help("Prints the changes that are occurring")
+}
+
+fn on_conflict() -> Arg<'static, 'static> {
+    Arg::with_name("on-conflict").short("c").long("on-conflict").takes_value(true)
+        .possible_values(&["skip", "overwrite", "suffix", "trash", "ask", "abort"])
+        .long_help(CONFLICT_HELP)
+}
+
+fn order() -> Arg<'static, 'static> {
+    Arg::with_name("order").long("order").takes_value(true)
+        .possible_values(&["aired", "dvd", "absolute", "alternate", "regional"])
+        .help("Sets the ordering of the episodes [default: aired]")
+        .long_help(ORDER_HELP)
+}
+
+fn max_depth() -> Arg<'static, 'static> {
+    Arg::with_name("max-depth").long("max-depth").value_name("DEPTH").takes_value(true)
+        .help("Sets how many levels of directories below a season are searched for episodes [default: 2]")
+}
+
+fn sort() -> Arg<'static, 'static> {
+    Arg::with_name("sort").long("sort").takes_value(true)
+        .possible_values(&["natural", "modified", "created", "file"])
+        .help("Sets the order in which the episodes of a season are numbered [default: natural]")
+        .long_help(SORT_HELP)
+}
+
+fn junk() -> Arg<'static, 'static> {
+    Arg::with_name("junk").long("junk").value_name("POLICY").takes_value(true)
+        .possible_values(&["exclude", "flag"])
+        .help("Sets what becomes of samples, trailers and other videos which are not episodes [default: exclude]")
+        .long_help(JUNK_HELP)
+}
+
+fn junk_durations() -> Arg<'static, 'static> {
+    Arg::with_name("junk-durations").long("junk-durations")
+        .help("Also takes videos much shorter than the others of their season as junk, by their durations")
+        .long_help(JUNK_DURATIONS_HELP)
+}
+
+fn format() -> Arg<'static, 'static> {
+    Arg::with_name("format").short("f").long("format").takes_value(true).default_value("text")
+        .possible_values(&["text", "json"])
+        .help("Sets the format of the output")
+        .long_help(FORMAT_HELP)
+}
+
+/// The flags shared by every subcommand which takes them.
+fn flags(matches: &ArgMatches) -> u8 {
+    let mut flags = 0;
+    if matches.is_present("dry-run") { flags |= DRY_RUN; }
+    if matches.is_present("verbose") { flags |= VERBOSE; }
+    if matches.is_present("force") { flags |= FORCE; }
+    if matches.value_of("format") == Some("json") { flags |= JSON; }
+    flags
+}
+
+/// Collects the arguments of `rename` and `preview`, taking those which were not given from the settings.
+fn arguments(matches: &ArgMatches, settings: &Settings, flags: u8) -> Arguments {
+    let flags = flags | self::flags(matches);
+
+    // Set to current working directory if no directory argument is given.
+    let base_directory = match matches.value_of("DIRECTORY") {
+        Some(directory) => directory.to_owned(),
+        None => env::current_dir().ok()
+            .and_then(|directory| directory.to_str().map(String::from))
+            .unwrap_or_else(|| fail(flags, ErrorKind::Other, "unable to get a valid current working directory"))
     };
 
-    for source in &season.episodes {
-        match backend::collect_target(source, season.season_no, episode_no, arguments, &api, series_id) {
-            Ok(target) => {
-                // If the target exists, do not overwrite the target without first asking if it is OK.
-                if target.exists() {
-                    let stderr = io::stderr();
-                    let mut stderr = stderr.lock();
-                    let _ = writeln!(stderr, "tv-renamer: episode to be renamed already exists:\n{:?}\nIs it okay to overwrite? (y/n)", &target);
-                    let mut input = [b'n'; 1];
-
-                    if let Err(why) = io::stdin().read_exact(&mut input) {
-                        let _ = writeln!(stderr, "tv-renamer: error reading from standard input: {:?}", why);
-                        process::exit(1);
-                    }
-
-                    if input[0] != b'y' {
-                        let _ = stderr.write_all(b"tv-renamer: stopping the renaming process.\n");
-                        process::exit(1);
-                    }
-                }
+    // If no series name was given, set the series name to the base directory
+    let series_name = match matches.value_of("series-name") {
+        Some(name) => name.to_owned(),
+        None => Path::new(&base_directory).file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| fail(flags, ErrorKind::Other, "unable to infer the series name from the directory"))
+    };
 
-                // If dry run or verbose is enabled, print the action being taken
-                if arguments.flags & (DRY_RUN + VERBOSE) != 0 {
-                    let _ = stdout.write(b"\x1b[1m\x1b[32m");
-                    let _ = write!(stdout, "{:?}", backend::shorten_path(&source));
-                    let _ = stdout.write(b"\x1b[0m -> ");
-                    let _ = stdout.write(b"\x1b[1m\x1b[32m");
-                    let _ = write!(stdout, "{:?}", backend::shorten_path(&target));
-                    let _ = stdout.write(b"\x1b[0m\n");
-                }
+    Arguments {
+        flags:            flags,
+        on_conflict:      matches.value_of("on-conflict").and_then(|policy| policy.parse().ok())
+            .or(settings.on_conflict).unwrap_or(ConflictPolicy::Ask),
+        action:           matches.value_of("action").and_then(|action| action.parse().ok())
+            .unwrap_or(settings.action),
+        ordering:         matches.value_of("order").and_then(|ordering| ordering.parse().ok())
+            .unwrap_or(settings.ordering),
+        season_index:     value_t!(matches, "season-number", u16).unwrap_or_else(|why| why.exit()),
+        episode_index:    value_or(matches, "episode-start", settings.episode_start),
+        pad_length:       value_or(matches, "pad-length", settings.pad_length),
+        base_directory:   base_directory,
+        target_directory: matches.value_of("target-directory").map(String::from)
+            .or_else(|| settings.target_directory.clone()),
+        scan:             scan_options(matches, settings),
+        match_runtime:    matches.is_present("match-runtime") || settings.match_runtime,
+        plan_out:         matches.value_of("plan-out").map(String::from),
+        emit_script:      matches.value_of("emit-script").and_then(|format| format.parse::<ScriptFormat>().ok()),
+        series_name:      series_name,
+        template:         tokenizer::tokenize_template(matches.value_of("template").unwrap_or(&settings.template)),
+    }
+}
 
-                // If dry run is not enabled, rename the file
-                if arguments.flags & DRY_RUN == 0 {
-                    if let Err(cause) = fs::rename(&source, &target) {
-                        let _ = writeln!(stderr, "tv-renamer: rename failed: {:?}", cause.to_string());
-                        process::exit(1);
-                    }
-                }
+/// Collects the options of a scan, taking those which were not given from the settings.
+fn scan_options(matches: &ArgMatches, settings: &Settings) -> ScanOptions {
+    let mut options = settings.scan_options();
+    options.max_depth = value_or(matches, "max-depth", options.max_depth);
+    options.sort = matches.value_of("sort").and_then(|order| order.parse().ok()).unwrap_or(options.sort);
+    options.junk = matches.value_of("junk").and_then(|policy| policy.parse().ok()).unwrap_or(options.junk);
+    options.junk_durations |= matches.is_present("junk-durations");
+    options
+}
+
+/// Parses the value of an option, which defaults to the given value when the option was not given.
+fn value_or<T: FromStr>(matches: &ArgMatches, name: &str, default: T) -> T {
+    if matches.is_present(name) {
+        value_t!(matches, name, T).unwrap_or_else(|why| why.exit())
+    } else {
+        default
+    }
+}
+
+/// Plans the renaming of every episode in the base directory, and carries out the plan.
+fn rename(arguments: Arguments, settings: &Settings) {
+    if let Err(failure) = rename_series(&arguments, &provider::open(settings)) {
+        fail(arguments.flags, failure.kind, failure.message);
+    }
+}
+
+/// Renames every series of a library, each of which is a directory named after its series, and prints a summary of
+/// the run. Every series is renamed as its own batch, so that each may be undone on its own. A series which fails
+/// is reported in the summary, and the remaining series are renamed regardless.
+fn library(matches: &ArgMatches, settings: &Settings, flags: u8) {
+    let root = self::arguments(matches, settings, flags);
+    let flags = root.flags;
+    let only = matches.values_of("only").map(|names| names.map(str::to_lowercase).collect::<Vec<_>>());
+    let directories = backend::scan_library(&root.base_directory).unwrap_or_else(|why| fail(flags, ErrorKind::Io, why));
+
+    // Previews do not record their progress, as nothing was renamed.
+    let mut progress = match matches.value_of("progress") {
+        Some(path) => Some(Progress::open(Path::new(path)).unwrap_or_else(|why| {
+            fail(flags, ErrorKind::Io, format!("unable to read the progress file {}: {}", path, why))
+        })),
+        None => None
+    };
 
+    let provider = provider::open(settings);
+    let mut outcomes = Vec::with_capacity(directories.len());
+    for directory in directories {
+        let name = directory.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        if only.as_ref().map_or(false, |only| !only.contains(&name.to_lowercase())) { continue }
+        if progress.as_ref().map_or(false, |progress| progress.is_complete(&name)) {
+            outcomes.push((name, Ok(None)));
+            continue
+        }
+
+        let mut arguments = self::arguments(matches, settings, flags);
+        arguments.enter_series(&directory);
+        arguments.series_name = name.clone();
+        let outcome = rename_series(&arguments, &provider);
+        if let (Ok(_), Some(progress)) = (outcome.as_ref(), progress.as_mut()) {
+            if flags & DRY_RUN == 0 {
+                progress.complete(&name).unwrap_or_else(|why| {
+                    fail(flags, ErrorKind::Io, format!("unable to record the progress of {}: {}", name, why))
+                });
+            }
+        }
+        outcomes.push((name, outcome.map(Some)));
+    }
+
+    let (mut renamed, mut files, mut resumed) = (0, 0, 0);
+    let mut failures = Vec::new();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for &(ref name, ref outcome) in &outcomes {
+        let (status, count, message) = match *outcome {
+            Ok(Some(count)) if flags & DRY_RUN != 0 => ("previewed", count, None),
+            Ok(Some(count)) => ("renamed", count, None),
+            Ok(None) => ("resumed", 0, None),
+            Err(ref failure) => ("failed", 0, Some(failure.message.as_str()))
+        };
+
+        match *outcome {
+            Ok(Some(count)) => { renamed += 1; files += count; },
+            Ok(None) => resumed += 1,
+            Err(ref failure) => failures.push(failure.kind)
+        }
+
+        if flags & JSON != 0 {
+            emit(&Event::Summary { series: name, status: status, files: count, message: message });
+        } else if let Some(message) = message {
+            let _ = writeln!(stdout, "{}: failed: {}", name, message);
+        } else if status == "resumed" {
+            let _ = writeln!(stdout, "{}: renamed by an earlier run", name);
+        } else {
+            let _ = writeln!(stdout, "{}: {} {} files", name, status, count);
+        }
+    }
+
+    if flags & JSON == 0 {
+        let verb = if flags & DRY_RUN != 0 { "would rename" } else { "renamed" };
+        let _ = writeln!(stdout, "tv-renamer: {} {} files of {} series; {} series were renamed by an earlier run, \
+            and {} failed", verb, files, renamed, resumed, failures.len());
+    }
+
+    if let Some(&kind) = failures.first() {
+        fail(flags, kind, format!("{} of {} series could not be renamed", failures.len(), outcomes.len()));
+    }
+}
+
+/// Plans the renaming of every episode of a series, and carries out the plan. Returns the number of files which
+/// were, or in a preview would be, given new names.
+fn rename_series<P: Provider>(arguments: &Arguments, provider: &P) -> Result<usize, Failure> {
+    // Collect a list of episodes within a directory and plan their renames.
+    let mut plan = RenamePlan::new(arguments.action);
+    plan.series_name = arguments.series_name.clone();
+    let scanned = backend::scan_directory(&arguments.base_directory, arguments.season_index, &arguments.scan)
+        .map_err(|why| Failure::new(ErrorKind::Io, why))?;
+
+    // Every season is named from the same episode guide, which is only requested once.
+    let series_id = provider::lookup(provider, &arguments.series_name)
+        .map_err(|why| Failure::new(ErrorKind::from(&why), why))?;
+    let guide = provider.episodes(series_id, arguments.ordering)
+        .map_err(|why| Failure::new(ErrorKind::from(&why), why))?;
+
+    let (mut seasons, mixed) = match scanned {
+        // If the directory contains episodes, they are numbered from the given episode.
+        ScanDir::Episodes(season) => (vec![(season, arguments.episode_index)], false),
+        // If the directory contains seasons, the episodes of each season are numbered from the first.
+        ScanDir::Seasons(seasons) => (seasons.into_iter().map(|season| (season, 1)).collect(), false),
+        // If the directory contains episodes of several seasons, they are numbered by their names.
+        ScanDir::Mixed(seasons) => (seasons.into_iter().map(|season| (season, 1)).collect(), true)
+    };
+
+    // Files which give no episode numbers may be numbered by their durations, rather than by their order.
+    if arguments.match_runtime {
+        for &mut (ref mut season, episode_start) in &mut seasons {
+            let matched = match runtime::propose(season, episode_start, &guide) {
+                Some(matched) => matched,
+                None => continue
+            };
+            let applied = matched.is_confident();
+            if applied { matched.apply(season); }
+
+            let percent = (matched.confidence * 100.0).round();
+            if arguments.flags & JSON != 0 {
+                emit(&Event::Matched { season: season.season_no, confidence: matched.confidence, applied: applied });
+            } else if arguments.flags & (DRY_RUN | VERBOSE) != 0 && applied {
+                let _ = writeln!(io::stdout(), "tv-renamer: {}: season {} is numbered by the runtimes of its episodes, \
+                    with {}% confidence", arguments.series_name, season.season_no, percent);
+            } else if arguments.flags & (DRY_RUN | VERBOSE) != 0 {
+                let _ = writeln!(io::stdout(), "tv-renamer: {}: season {} is numbered in order, as the runtimes of its \
+                    episodes are ambiguous ({}% confidence)", arguments.series_name, season.season_no, percent);
+            }
+        }
+    }
+
+    // The seasons of a mixed directory are numbered by the names of their files, so that a missing episode leaves a
+    // gap rather than shifting every episode after it. A season in which some names give no number is numbered in
+    // order only when forced.
+    let mut numbered = Vec::new();
+    for (season, episode_start) in seasons {
+        let numbers = match season.numbers_by_name() {
+            Some(numbers) if mixed => numbers,
+            None if mixed && arguments.flags & FORCE == 0 => {
+                return Err(Failure::new(ErrorKind::Check, format!("not every file of season {} gives its episode \
+                    number, so they cannot be numbered by name; number them in order with --force", season.season_no)))
             },
-            Err(why) => {
-                let _ = stderr.write(b"tv-renamer: ");
-                match why {
-                    // The episode number was unable to be found in the TV series.
-                    TargetErr::EpisodeDoesNotExist => {
-                        let _ = writeln!(stderr, "unable to find episode {}", episode_no);
-                    },
-                    TargetErr::Extension => {
-                        let _ = writeln!(stderr, "unable to get extension");
-                    },
-                    TargetErr::Parent => {
-                        let _ = writeln!(stderr, "unable to get parent filepath");
-                    }
+            _ => season.numbers_in_order(episode_start)
+        };
+        numbered.push((season, numbers));
+    }
+
+    // Episodes are numbered by their position, so every season is compared with the guide before anything is planned.
+    let mut problems = false;
+    for &(ref season, ref numbers) in &numbered {
+        // Samples, trailers and other videos which were left out of the numbering are listed by previews.
+        if arguments.scan.junk == JunkPolicy::Exclude {
+            for junk in &season.junk {
+                if arguments.flags & JSON != 0 {
+                    emit(&Event::Excluded { source: &junk.path, reason: junk.kind.as_str() });
+                } else if arguments.flags & (DRY_RUN | VERBOSE) != 0 {
+                    let _ = writeln!(io::stdout(), "tv-renamer: {}: excluded {}, as it is {}", arguments.series_name,
+                        junk.path.display(), junk.kind.description());
                 }
-                process::exit(1);
             }
         }
-        episode_no += 1;
-    }
-}
-
-enum ParseError {
-    NoEpisodeIndex,
-    NoSeriesIndex,
-    NoSeriesName,
-    NoTemplate,
-    NoPadLength,
-    EpisodeIndexIsNaN(String),
-    SeriesIndexIsNaN(String),
-    PadLengthIsNaN(String),
-    InvalidArgument(String),
-    TooManyArguments(String),
-    NoCWD,
-    CWDNotValid,
-}
-
-/// Parse command-line arguments and update the `arguments` structure accordingly.
-fn parse_arguments <A: Iterator<Item = String>> (
-    arguments: &mut Arguments,
-    mut iterator: A
-) -> Result<(), ParseError> {
-    while let Some(argument) = iterator.next() {
-        if argument.starts_with('-') {
-            match argument.as_str() {
-                "-h" | "--help" => {
-                    println!("{}", MAN_PAGE);
-                    process::exit(0);
-                }
-                "-d" | "--dry-run" => arguments.flags |= DRY_RUN,
-                "-e" | "--episode-start" => {
-                    let value = iterator.next().ok_or(ParseError::NoEpisodeIndex)?;
-                    arguments.episode_index = value.parse::<u16>()
-                        .map_err(|_| ParseError::EpisodeIndexIsNaN(value))?;
-                },
-                "-n" | "--series-name" => {
-                    arguments.series_name.push_str(&iterator.next().ok_or(ParseError::NoSeriesName)?);
-                },
-                "-s" | "--season-number" => {
-                    let value = iterator.next().ok_or(ParseError::NoSeriesIndex)?;
-                    arguments.season_index = value.parse::<u8>()
-                        .map_err(|_| ParseError::SeriesIndexIsNaN(value))?;
-                },
-                "-t" | "--template" => {
-                    let value = iterator.next().ok_or(ParseError::NoTemplate)?;
-                    arguments.template = tokenizer::tokenize_template(&value);
-                },
-                "-p" | "--pad-length" => {
-                    let value = iterator.next().ok_or(ParseError::NoPadLength)?;
-                    arguments.pad_length = value.parse::<u8>()
-                        .map_err(|_| ParseError::PadLengthIsNaN(value))?;
-                },
-                "-v" | "--verbose" => arguments.flags |= VERBOSE,
-                _ => return Err(ParseError::InvalidArgument(argument))
+
+        let report = preflight::check(season, numbers, &guide);
+        if report.is_empty() { continue }
+        problems |= report.has_problems();
+        if arguments.flags & JSON != 0 {
+            emit(&Event::Check(&report));
+        } else {
+            let _ = writeln!(io::stderr(), "tv-renamer: {}: {}", arguments.series_name, report);
+        }
+    }
+
+    if problems && arguments.flags & FORCE == 0 {
+        return Err(Failure::new(ErrorKind::Check,
+            "the episodes do not match the episode guide; rename them anyway with --force"));
+    }
+
+    for (season, numbers) in numbered {
+        plan_season(&season, arguments, &numbers, series_id, &guide, &mut plan)?;
+    }
+
+    // Decide what to do with files that already exist, and ensure that no two files are renamed to the same name.
+    let skipped = plan.resolve_conflicts(arguments.on_conflict, ask_conflict)
+        .and_then(|skipped| plan.schedule().map(|_| skipped))
+        .map_err(|why| Failure::new(ErrorKind::from(&why), why))?;
+
+    // The plan may be written out for review, and carried out later with `tv-renamer apply`.
+    if let Some(ref path) = arguments.plan_out {
+        if let Err(why) = export::save(&plan, Path::new(path)) {
+            return Err(Failure::new(ErrorKind::Io, format!("unable to write the plan to {}: {}", path, why)));
+        }
+    }
+
+    // Operators may instead carry out the plan with a script, on systems which cannot run this program.
+    if let Some(format) = arguments.emit_script {
+        let script = script::generate(format, &plan)
+            .map_err(|why| Failure::new(ErrorKind::Other, format!("unable to generate a script: {}", why)))?;
+        let _ = io::stdout().write(script.as_bytes());
+        return Ok(0)
+    }
+
+    // Episodes are numbered by their position, so a preview states the order that they were sorted in.
+    if arguments.flags & (DRY_RUN | JSON) == DRY_RUN {
+        let _ = writeln!(io::stdout(), "tv-renamer: episodes are numbered in {}", arguments.scan.sort.description());
+    }
+
+    commit(&plan, &skipped, arguments.flags)
+}
+
+/// Carries out a plan that was written by `--plan-out`, and possibly edited since. As the files may have changed in
+/// the meantime, the plan is validated again before anything is renamed.
+fn apply(matches: &ArgMatches) {
+    let flags = flags(matches);
+    let on_conflict = matches.value_of("on-conflict").and_then(|policy| policy.parse().ok())
+        .unwrap_or(ConflictPolicy::Abort);
+    let path = matches.value_of("PLAN").unwrap_or_default();
+
+    let mut plan = export::load(Path::new(path)).unwrap_or_else(|why| {
+        fail(flags, ErrorKind::Io, format!("unable to read the plan from {}: {}", path, why))
+    });
+
+    let skipped = plan.validate()
+        .and_then(|_| plan.resolve_conflicts(on_conflict, ask_conflict))
+        .and_then(|skipped| plan.schedule().map(|_| skipped))
+        .unwrap_or_else(|why| fail(flags, ErrorKind::from(&why), why));
+
+    if let Err(failure) = commit(&plan, &skipped, flags) {
+        fail(flags, failure.kind, failure.message);
+    }
+}
+
+/// Prints the plan if requested, and unless this is a dry run, carries it out and journals it. Returns the number of
+/// files which were, or would be, given new names.
+fn commit(plan: &RenamePlan, skipped: &[Operation], flags: u8) -> Result<usize, Failure> {
+    if flags & JSON != 0 {
+        for operation in skipped {
+            emit(&Event::Skipped { source: &operation.source, target: &operation.target, reason: "conflict" });
+        }
+        for operation in &plan.displaced {
+            emit(&Event::Planned { action: "trash", source: &operation.target, target: None,
+                series_id: operation.series_id, season: operation.season_no, episode: operation.episode_no });
+        }
+        for operation in &plan.operations {
+            emit(&Event::planned(plan.action.as_str(), operation, Some(&operation.target)));
+        }
+    // If dry run or verbose is enabled, print the actions being taken
+    } else if flags & (DRY_RUN + VERBOSE) != 0 {
+        let stdout = &mut io::stdout();
+        for operation in &plan.displaced {
+            let _ = stdout.write(b"\x1b[1m\x1b[33m");
+            let _ = write!(stdout, "{:?}", backend::shorten_path(&operation.target));
+            let _ = stdout.write(b"\x1b[0m -> Trash\n");
+        }
+
+        for operation in &plan.operations {
+            let _ = stdout.write(b"\x1b[1m\x1b[32m");
+            let _ = write!(stdout, "{:?}", backend::shorten_path(&operation.source));
+            let _ = stdout.write(b"\x1b[0m -> ");
+            let _ = stdout.write(b"\x1b[1m\x1b[32m");
+            let _ = write!(stdout, "{:?}", backend::shorten_path(&operation.target));
+            let _ = stdout.write(b"\x1b[0m\n");
+        }
+    }
+
+    // If dry run is not enabled, rename every file as a single transaction, which is rolled back if any rename fails.
+    if flags & DRY_RUN != 0 || plan.is_empty() { return Ok(plan.operations.len()) }
+    let outcome = plan.execute().map_err(|why| Failure::new(ErrorKind::from(&why), why))?;
+
+    // Every batch of renames is journaled so that it may be undone later.
+    let mut batch = Batch::new(&plan.series_name);
+    if let Err(why) = batch.record(plan, outcome) {
+        return Err(Failure::new(ErrorKind::Io, format!("unable to record renames for undo: {}", why)));
+    }
+
+    if flags & JSON != 0 {
+        for operation in &plan.operations {
+            emit(&Event::Renamed { source: &operation.source, target: &operation.target, batch: &batch.id });
+        }
+    } else if flags & VERBOSE != 0 {
+        let _ = writeln!(io::stdout(), "tv-renamer: renamed {} files; revert with `tv-renamer undo {}`",
+            batch.entries.len(), batch.id);
+    }
+    Ok(plan.operations.len())
+}
+
+/// Reverts the batch with the given ID, or the most recent batch if no ID was given.
+fn undo(matches: &ArgMatches) {
+    let flags = flags(matches);
+    match journal::load(matches.value_of("BATCH-ID")).and_then(|batch| batch.undo().map(|count| (batch, count))) {
+        Ok((ref batch, count)) if flags & JSON != 0 => emit(&Event::Reverted {
+            batch: &batch.id, count: count, restored: batch.trashed.len(), overwritten: batch.overwritten.len()
+        }),
+        Ok((batch, count)) => {
+            let _ = writeln!(io::stdout(), "tv-renamer: reverted {} files renamed by batch {}", count, batch.id);
+            if !batch.trashed.is_empty() {
+                let _ = writeln!(io::stdout(), "tv-renamer: restored {} files from the Trash", batch.trashed.len());
+            }
+            for path in &batch.overwritten {
+                let _ = writeln!(io::stderr(), "tv-renamer: {:?} was overwritten by the batch, and cannot be restored",
+                    path);
             }
-        } else if arguments.base_directory.is_empty() {
-            arguments.base_directory = argument;
+        },
+        Err(why) => fail(flags, ErrorKind::from(&why), why)
+    }
+}
+
+/// Prints the ID and name of every series on TVDB that matches the given name.
+fn search(matches: &ArgMatches, settings: &Settings) {
+    let flags = flags(matches);
+    let name = matches.value_of("NAME").unwrap_or_default();
+    let results = provider::open(settings).search(name).unwrap_or_else(|why| fail(flags, ErrorKind::from(&why), why));
+
+    if results.is_empty() {
+        fail(flags, ErrorKind::SeriesNotFound, format!("invalid TV series: {}", name));
+    }
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for series in &results {
+        if flags & JSON != 0 {
+            emit(&Event::Series { id: series.id, name: &series.name });
+        } else if let Some(ref year) = series.year {
+            let _ = writeln!(stdout, "{:>8}  {} ({})", series.id, series.name, year);
         } else {
-            return Err(ParseError::TooManyArguments(argument));
+            let _ = writeln!(stdout, "{:>8}  {}", series.id, series.name);
         }
     }
+}
 
-    // Set to current working directory if no directory argument is given.
-    if arguments.base_directory.is_empty() {
-        let directory = env::current_dir().map_err(|_| ParseError::NoCWD)?;
-        arguments.base_directory = directory.to_str().ok_or(ParseError::CWDNotValid)?.to_owned();
+/// Prints the episode guide of a series in the given ordering, as it would be used to name its episodes.
+fn episodes(matches: &ArgMatches, settings: &Settings) {
+    let flags = flags(matches);
+    let series = matches.value_of("SERIES").unwrap_or_default();
+    let ordering = matches.value_of("order").and_then(|ordering| ordering.parse().ok()).unwrap_or(settings.ordering);
+    let season = if matches.is_present("season") {
+        Some(value_t!(matches, "season", u16).unwrap_or_else(|why| why.exit()))
+    } else {
+        None
+    };
+
+    let mut provider = provider::open(settings);
+    provider.refresh = matches.is_present("refresh");
+    let series_id = if matches.is_present("id") {
+        series.parse::<u32>().unwrap_or_else(|_| fail(flags, ErrorKind::Other, format!("invalid TVDB ID: {}", series)))
+    } else {
+        provider::lookup(&provider, series).unwrap_or_else(|why| fail(flags, ErrorKind::from(&why), why))
+    };
+
+    let guide = provider.episodes(series_id, ordering).unwrap_or_else(|why| fail(flags, ErrorKind::from(&why), why));
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    if flags & JSON == 0 {
+        let _ = writeln!(stdout, "Season  Episode  Absolute  Aired       Title");
     }
 
-    // If no series name was given, set the series name to the base directory
-    if arguments.series_name.is_empty() {
-        arguments.series_name = String::from(Path::new(&arguments.base_directory)
-            .file_name().unwrap().to_str().unwrap());
+    for episode in guide.iter().filter(|episode| season.map_or(true, |season| episode.season_no == season)) {
+        if flags & JSON != 0 {
+            emit(&Event::Episode {
+                series_id:   series_id,
+                season:      episode.season_no,
+                episode:     episode.episode_no,
+                absolute:    episode.absolute_no,
+                title:       &episode.title,
+                first_aired: episode.first_aired.as_deref(),
+            });
+        } else {
+            let absolute = episode.absolute_no.map_or_else(String::new, |number| number.to_string());
+            let _ = writeln!(stdout, "{:>6}  {:>7}  {:>8}  {:<10}  {}", episode.season_no, episode.episode_no,
+                absolute, episode.first_aired.as_ref().map_or("", String::as_str), episode.title);
+        }
     }
+}
+
+/// Compares every series of a library with its episode guide, and writes a report of the missing, unaired and extra
+/// episodes. A series which cannot be compared is noted in the report, rather than failing the run.
+fn report(matches: &ArgMatches, settings: &Settings) {
+    let flags = flags(matches);
+    let format = matches.value_of("format").and_then(|format| format.parse().ok()).unwrap_or(ReportFormat::Markdown);
+    let ordering = matches.value_of("order").and_then(|ordering| ordering.parse().ok()).unwrap_or(settings.ordering);
+    let library = matches.value_of("LIBRARY").map(PathBuf::from).or_else(|| env::current_dir().ok())
+        .unwrap_or_else(|| fail(flags, ErrorKind::Other, "unable to get a valid current working directory"));
+    let directories = backend::scan_library(&library).unwrap_or_else(|why| fail(flags, ErrorKind::Io, why));
+
+    let mut provider = provider::open(settings);
+    provider.refresh = matches.is_present("refresh");
+    let options = scan_options(matches, settings);
+    let today = report::today();
+    let reports = directories.into_iter().map(|directory| {
+        let series = directory.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut report = SeriesReport::new(series, directory);
+        let scanned = match backend::scan_directory(&report.directory, 1, &options) {
+            Ok(scanned) => scanned,
+            Err(why) => {
+                report.error = Some(why.to_string());
+                return report
+            }
+        };
 
+        let guide = provider::lookup(&provider, &report.series)
+            .and_then(|series_id| provider.episodes(series_id, ordering).map(|guide| (series_id, guide)));
+        match guide {
+            Ok((series_id, guide)) => {
+                report.series_id = Some(series_id);
+                report.compare(&scanned, settings.episode_start, &guide, &today);
+            },
+            Err(why) => report.error = Some(why.to_string())
+        }
+        report
+    }).collect::<Vec<SeriesReport>>();
+
+    let rendered = report::render(format, &reports);
+    match matches.value_of("output") {
+        Some(path) => if let Err(why) = fs::write(path, rendered) {
+            fail(flags, ErrorKind::Io, format!("unable to write the report to {}: {}", path, why));
+        },
+        None => { let _ = io::stdout().write(rendered.as_bytes()); }
+    }
+}
+
+/// Plans the renaming of all of the episodes in given season
+fn plan_season(season: &Season, arguments: &Arguments, numbers: &[u32], series_id: u32, guide: &[EpisodeInfo],
+    plan: &mut RenamePlan) -> Result<(), Failure>
+{
+    for (episode, &episode_no) in season.episodes.iter().zip(numbers) {
+        let source = &episode.path;
+        match backend::collect_target(source, season.season_no, episode_no, arguments, guide) {
+            Ok(target) => {
+                // Companion files follow the video to its new stem, keeping their language and flag suffixes.
+                let sidecars = episode.sidecars.iter()
+                    .filter_map(|sidecar| sidecar::target(source, &target, sidecar).map(|target| (sidecar, target)))
+                    .collect::<Vec<_>>();
+
+                plan.push(Operation {
+                    source: source.clone(), target: target, series_id: series_id,
+                    season_no: season.season_no, episode_no: episode_no
+                });
+
+                for (sidecar, target) in sidecars {
+                    plan.push(Operation {
+                        source: sidecar.clone(), target: target, series_id: series_id,
+                        season_no: season.season_no, episode_no: episode_no
+                    });
+                }
+            },
+            Err(why) => match why {
+                // The episode number was unable to be found in the TV series.
+                // Files which were reported as extra keep their names when renaming is forced.
+                TargetErr::EpisodeDoesNotExist if arguments.flags & FORCE != 0 => (),
+                TargetErr::EpisodeDoesNotExist => {
+                    let message = format!("unable to find episode {}", episode_no);
+                    return Err(Failure::new(ErrorKind::EpisodeMissing, message))
+                },
+                TargetErr::Extension => return Err(Failure::new(ErrorKind::Other, "unable to get extension")),
+                TargetErr::Parent    => return Err(Failure::new(ErrorKind::Other, "unable to get parent filepath"))
+            }
+        }
+    }
     Ok(())
 }
+
+/// Asks the user what to do about an existing target. Anything other than a recognized answer aborts.
+fn ask_conflict(operation: &Operation) -> ConflictPolicy {
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    let _ = writeln!(stderr, "tv-renamer: episode to be renamed already exists:\n{:?}\n\
+        [s]kip, [o]verwrite, keep both with a numbered [n]ame, move the existing file to the [t]rash, or [a]bort?",
+        operation.target);
+
+    let mut input = String::new();
+    if let Err(why) = io::stdin().read_line(&mut input) {
+        let _ = writeln!(stderr, "tv-renamer: error reading from standard input: {:?}", why);
+        return ConflictPolicy::Abort;
+    }
+
+    match input.trim() {
+        "s" | "skip"      => ConflictPolicy::Skip,
+        "o" | "overwrite" => ConflictPolicy::Overwrite,
+        "n" | "name"      => ConflictPolicy::Suffix,
+        "t" | "trash"     => ConflictPolicy::Trash,
+        _                 => ConflictPolicy::Abort
+    }
+}
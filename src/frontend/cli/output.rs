@@ -0,0 +1,250 @@
+use backend::JSON;
+use backend::journal::JournalError;
+use backend::plan::{Operation, PlanError};
+use backend::preflight::Report;
+use backend::provider::ProviderError;
+use serde::Serializer;
+use serde_json;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+/// Exit status of a failure which has no status of its own, such as invalid arguments.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit status when the series could not be found on TVDB.
+pub const EXIT_SERIES_NOT_FOUND: i32 = 2;
+/// Exit status when an episode could not be found within its series on TVDB.
+pub const EXIT_EPISODE_MISSING: i32 = 3;
+/// Exit status when files would be renamed over existing files, or over each other.
+pub const EXIT_CONFLICT: i32 = 4;
+/// Exit status when a file or directory could not be read or written.
+pub const EXIT_IO: i32 = 5;
+/// Exit status when the episodes of a directory do not match the episode guide.
+pub const EXIT_CHECK: i32 = 6;
+
+/// The kind of failure reported by an `error` event, which determines the exit status.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Other,
+    SeriesNotFound,
+    EpisodeMissing,
+    Conflict,
+    Io,
+    Check,
+}
+
+impl ErrorKind {
+    pub fn exit_status(&self) -> i32 {
+        match *self {
+            ErrorKind::Other          => EXIT_FAILURE,
+            ErrorKind::SeriesNotFound => EXIT_SERIES_NOT_FOUND,
+            ErrorKind::EpisodeMissing => EXIT_EPISODE_MISSING,
+            ErrorKind::Conflict       => EXIT_CONFLICT,
+            ErrorKind::Io             => EXIT_IO,
+            ErrorKind::Check          => EXIT_CHECK,
+        }
+    }
+}
+
+impl<'a> From<&'a PlanError> for ErrorKind {
+    fn from(error: &PlanError) -> ErrorKind {
+        match *error {
+            PlanError::DuplicateSource(..) | PlanError::DuplicateTarget(..) | PlanError::TargetExists(..) => {
+                ErrorKind::Conflict
+            },
+            _ => ErrorKind::Io
+        }
+    }
+}
+
+impl<'a> From<&'a JournalError> for ErrorKind {
+    fn from(error: &JournalError) -> ErrorKind {
+        match *error {
+            JournalError::NoStateDir | JournalError::NothingToUndo | JournalError::BatchNotFound(_)
+                | JournalError::InvalidBatchId(_) => ErrorKind::Other,
+            JournalError::TargetMissing(_) | JournalError::TargetChanged(_) | JournalError::SourceOccupied(_)
+                | JournalError::TrashMissing(_) => {
+                ErrorKind::Conflict
+            },
+            JournalError::RevertFailed(ref why) => ErrorKind::from(why),
+            _ => ErrorKind::Io
+        }
+    }
+}
+
+impl<'a> From<&'a ProviderError> for ErrorKind {
+    fn from(error: &ProviderError) -> ErrorKind {
+        match *error {
+            ProviderError::SeriesNotFound(_) => ErrorKind::SeriesNotFound,
+            ProviderError::Cache(_)          => ErrorKind::Io,
+            _                                => ErrorKind::Other
+        }
+    }
+}
+
+/// A failure which ends the renaming of a series, and which ends the run unless other series remain to be renamed.
+#[derive(Debug)]
+pub struct Failure {
+    pub kind:    ErrorKind,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new<D: Display>(kind: ErrorKind, message: D) -> Failure {
+        Failure { kind: kind, message: message.to_string() }
+    }
+}
+
+/// A single line of output in the JSON format.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// A file will be placed at a new name, or moved into the Trash if the action is `trash`.
+    Planned {
+        action:     &'a str,
+        #[serde(serialize_with = "lossy")]
+        source:     &'a Path,
+        #[serde(skip_serializing_if = "Option::is_none", serialize_with = "lossy_option")]
+        target:     Option<&'a Path>,
+        series_id:  u32,
+        season:     u16,
+        episode:    u32,
+    },
+    /// A file was placed at its new name, and may be reverted by undoing the batch.
+    Renamed {
+        #[serde(serialize_with = "lossy")]
+        source:     &'a Path,
+        #[serde(serialize_with = "lossy")]
+        target:     &'a Path,
+        batch:      &'a str,
+    },
+    /// A file will keep its name, as its new name is already taken.
+    Skipped {
+        #[serde(serialize_with = "lossy")]
+        source:     &'a Path,
+        #[serde(serialize_with = "lossy")]
+        target:     &'a Path,
+        reason:     &'a str,
+    },
+    /// A video will not be renamed, as it appears to be a `sample`, `trailer`, `bonus` material, or is too `small` to
+    /// be an episode.
+    Excluded {
+        #[serde(serialize_with = "lossy")]
+        source:     &'a Path,
+        reason:     &'a str,
+    },
+    /// The durations of the files of a season were compared with the runtimes of its episodes, which number the files
+    /// if the comparison was confident enough to be `applied`.
+    Matched {
+        season:     u16,
+        confidence: f64,
+        applied:    bool,
+    },
+    /// A series which matched the name that was searched for.
+    Series {
+        id:         u32,
+        name:       &'a str,
+    },
+    /// An episode of the guide printed by `episodes`.
+    Episode {
+        series_id:   u32,
+        season:      u16,
+        episode:     u32,
+        absolute:    Option<u32>,
+        title:       &'a str,
+        first_aired: Option<&'a str>,
+    },
+    /// The episodes of a season do not match the episode guide.
+    Check(&'a Report),
+    /// The outcome of a series of a library, which is `renamed`, `previewed`, `resumed` (it was renamed by an earlier
+    /// run) or `failed`.
+    Summary {
+        series:     &'a str,
+        status:     &'a str,
+        files:      usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message:    Option<&'a str>,
+    },
+    /// A batch of renames was reverted.
+    Reverted {
+        batch:       &'a str,
+        count:       usize,
+        /// The number of files which the batch moved into the Trash, and which were restored.
+        restored:    usize,
+        /// The number of files which the batch overwrote, and which could not be restored.
+        overwritten: usize,
+    },
+    Error {
+        kind:       ErrorKind,
+        message:    String,
+    },
+}
+
+impl<'a> Event<'a> {
+    pub fn planned(action: &'a str, operation: &'a Operation, target: Option<&'a Path>) -> Event<'a> {
+        Event::Planned {
+            action:    action,
+            source:    &operation.source,
+            target:    target,
+            series_id: operation.series_id,
+            season:    operation.season_no,
+            episode:   operation.episode_no,
+        }
+    }
+}
+
+/// Writes an event to standard output as a single line of JSON. An event which cannot be written as JSON is replaced
+/// by an `error` event, so that no event is left out without a trace.
+pub fn emit(event: &Event) {
+    let line = serde_json::to_string(event).unwrap_or_else(|why| {
+        let message = format!("unable to write an event as JSON: {}", why);
+        serde_json::to_string(&Event::Error { kind: ErrorKind::Other, message: message })
+            .expect("error events are always valid JSON")
+    });
+    let _ = writeln!(io::stdout(), "{}", line);
+}
+
+/// Writes a path as a JSON string, replacing any part of it which is not valid UTF-8, as serde refuses such paths.
+fn lossy<S: Serializer>(path: &&Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+fn lossy_option<S: Serializer>(path: &Option<&Path>, serializer: S) -> Result<S::Ok, S::Error> {
+    match *path {
+        Some(path) => serializer.serialize_some(&path.to_string_lossy()),
+        None => serializer.serialize_none()
+    }
+}
+
+/// Reports a failure, as an `error` event when the output is JSON, and exits with the status of its kind.
+pub fn fail<D: Display>(flags: u8, kind: ErrorKind, message: D) -> ! {
+    if flags & JSON != 0 {
+        emit(&Event::Error { kind: kind, message: message.to_string() });
+    } else {
+        let _ = writeln!(io::stderr(), "tv-renamer: {}", message);
+    }
+    process::exit(kind.exit_status());
+}
+
+#[test]
+fn test_events() {
+    let event = Event::Skipped { source: Path::new("a.mkv"), target: Path::new("b.mkv"), reason: "conflict" };
+    assert_eq!(serde_json::to_string(&event).unwrap(),
+        r#"{"event":"skipped","source":"a.mkv","target":"b.mkv","reason":"conflict"}"#);
+
+    let event = Event::Error { kind: ErrorKind::SeriesNotFound, message: String::from("not found") };
+    assert_eq!(serde_json::to_string(&event).unwrap(),
+        r#"{"event":"error","kind":"series_not_found","message":"not found"}"#);
+
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let event = Event::Excluded { source: Path::new(OsStr::from_bytes(b"a\xFF.mkv")), reason: "sample" };
+        assert_eq!(serde_json::to_string(&event).unwrap(),
+            "{\"event\":\"excluded\",\"source\":\"a\u{FFFD}.mkv\",\"reason\":\"sample\"}");
+    }
+}
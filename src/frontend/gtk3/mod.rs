@@ -1,16 +1,21 @@
-use backend::{self, Arguments, ScanDir, Season, tokenizer, DRY_RUN};
+use backend::{self, Arguments, ScanDir, Season, TargetErr, preflight, runtime, sidecar, tokenizer, DRY_RUN};
+use backend::preflight::Report;
+use backend::journal::{self, Batch, JournalError};
+use backend::junk::JunkPolicy;
+use backend::config::Settings;
+use backend::conflict::ConflictPolicy;
+use backend::plan::{Operation, PlanError, RenamePlan};
+use backend::provider::{self, EpisodeInfo, Provider, ProviderError};
 
 use gdk::keys::constants as key;
 use gtk::prelude::*;
 use gtk::{
-    self, Builder, Button, Entry, FileChooserDialog, ListStore,
+    self, Builder, Button, ComboBoxText, Entry, FileChooserDialog, ListStore, MessageDialog,
     SpinButton, TreeView, TreeViewColumn, Window, WindowType
 };
 
 use std::error::Error;
-use std::fs;
-use std::path::{Path, PathBuf};
-use tvdb;
+use std::path::Path;
 use glib::Type;
 
 /// Allow drag-and-drop support in the directory entry text field by fixing the URI generated by dropped files.
@@ -26,7 +31,7 @@ fn parse_directory(directory: &str) -> String {
     output
 }
 
-pub fn interface() {
+pub fn interface(settings: Settings) {
     gtk::init().unwrap_or_else(|_| panic!("tv-renamer: failed to initialize GTK."));
 
     // Open the Glade GTK UI and import key GTK objects from the UI.
@@ -34,17 +39,26 @@ pub fn interface() {
     let window: Window                  = builder.get_object("main_window").unwrap();
     let preview_button: Button          = builder.get_object("preview_button").unwrap();
     let rename_button: Button           = builder.get_object("rename_button").unwrap();
+    let undo_button: Button             = builder.get_object("undo_button").unwrap();
     let series_name_entry: Entry        = builder.get_object("series_name_entry").unwrap();
     let series_directory_entry: Entry   = builder.get_object("series_directory_entry").unwrap();
     let template_entry: Entry           = builder.get_object("template_entry").unwrap();
     let series_directory_button: Button = builder.get_object("series_directory_button").unwrap();
     let episode_spin_button: SpinButton = builder.get_object("episode_spin_button").unwrap();
     let season_spin_button: SpinButton  = builder.get_object("season_spin_button").unwrap();
+    let conflict_combo: ComboBoxText    = builder.get_object("conflict_combo").unwrap();
     let preview_tree: TreeView          = builder.get_object("preview_tree").unwrap();
     let info_bar: gtk::InfoBar          = builder.get_object("info_bar").unwrap();
     let info_button: Button             = builder.get_object("info_close").unwrap();
     let notification_label: gtk::Label  = builder.get_object("notification_label").unwrap();
 
+    // The configured settings replace the defaults of the interface.
+    template_entry.set_text(&settings.template);
+    episode_spin_button.set_value(settings.episode_start as f64);
+    if let Some(policy) = settings.on_conflict {
+        conflict_combo.set_active_id(Some(policy.as_str()));
+    }
+
     // TreeView's List Store
     // Link these up to the preview_tree and then start renaming
     let preview_list = ListStore::new(&[Type::String, Type::String]);
@@ -83,6 +97,8 @@ pub fn interface() {
             let info_bar            = info_bar.clone();
             let notification_label  = notification_label.clone();
             let template_entry      = template_entry.clone();
+            let conflict_combo      = conflict_combo.clone();
+            let settings            = settings.clone();
             $widget.connect_clicked(move |_| {
                 if $dialog {
                     // Open file chooser dialog to modify series_directory_entry.
@@ -104,13 +120,23 @@ pub fn interface() {
                 }
                 let directory = directory_entry.get_text();
                 let mut program = &mut Arguments {
-                    flags:          if $dry_run { DRY_RUN } else { 0 },
-                    base_directory: parse_directory(&directory),
-                    series_name:    series_entry.get_text().to_string(),
-                    season_index:   season_spin_button.get_value_as_int() as u8,
-                    episode_index:  episode_spin_button.get_value_as_int() as u16,
-                    pad_length:     2,
-                    template:       tokenizer::tokenize_template(template_entry.get_text().as_str())
+                    flags:            if $dry_run { DRY_RUN } else { 0 },
+                    on_conflict:      conflict_combo.get_active_id()
+                        .and_then(|policy| policy.as_str().parse::<ConflictPolicy>().ok())
+                        .unwrap_or(ConflictPolicy::Abort),
+                    action:           settings.action,
+                    ordering:         settings.ordering,
+                    base_directory:   parse_directory(&directory),
+                    target_directory: settings.target_directory.clone(),
+                    scan:             settings.scan_options(),
+                    match_runtime:    settings.match_runtime,
+                    plan_out:         None,
+                    emit_script:      None,
+                    series_name:      series_entry.get_text().to_string(),
+                    season_index:     season_spin_button.get_value_as_int() as u16,
+                    episode_index:    episode_spin_button.get_value_as_int() as u32,
+                    pad_length:       settings.pad_length,
+                    template:         tokenizer::tokenize_template(template_entry.get_text().as_str())
                 };
 
                 if program.series_name.is_empty() {
@@ -120,7 +146,7 @@ pub fn interface() {
                 }
 
                 if !program.base_directory.is_empty() {
-                    rename_series(&program, &preview_list, &info_bar, &notification_label);
+                    rename_series(&program, &settings, &preview_list, &info_bar, &notification_label);
                 }
             });
         }}
@@ -131,6 +157,38 @@ pub fn interface() {
     rename_action!(series_directory_button, true, true);
     rename_action!(rename_button, false, false);
 
+    { // Revert the most recent batch of renames
+        let preview_list       = preview_list.clone();
+        let info_bar           = info_bar.clone();
+        let notification_label = notification_label.clone();
+        undo_button.connect_clicked(move |_| {
+            preview_list.clear();
+            match journal::load(None).and_then(|batch| batch.undo().map(|_| batch)) {
+                Ok(batch) => {
+                    for entry in &batch.entries {
+                        update_preview(&preview_list, &entry.target, &entry.source);
+                    }
+                    for trashed in &batch.trashed {
+                        update_preview(&preview_list, Path::new("Trash"), &trashed.original);
+                    }
+                    if batch.overwritten.is_empty() {
+                        info_bar.set_message_type(gtk::MessageType::Info);
+                        notification_label.set_text("Undo Success");
+                    } else {
+                        info_bar.set_message_type(gtk::MessageType::Warning);
+                        notification_label.set_text(&format!("Undo Success, but {} overwritten files could not be \
+                            restored", batch.overwritten.len()));
+                    }
+                },
+                Err(why) => {
+                    info_bar.set_message_type(gtk::MessageType::Error);
+                    notification_label.set_text(&why.to_string());
+                }
+            }
+            info_bar.show();
+        });
+    }
+
     { // Hide the Info Bar when the Info Bar is closed
         let info_bar = info_bar.clone();
         info_button.connect_clicked(move |_| {
@@ -161,39 +219,128 @@ pub fn interface() {
 }
 
 /// Attempt to rename all of the seasons within a given series
-fn rename_series(args: &Arguments, preview_list: &ListStore, info_bar: &gtk::InfoBar, notification_label: &gtk::Label) {
+fn rename_series(args: &Arguments, settings: &Settings, preview_list: &ListStore, info_bar: &gtk::InfoBar,
+    notification_label: &gtk::Label)
+{
     preview_list.clear();
-    match backend::scan_directory(&args.base_directory, args.season_index) {
-        Ok(ScanDir::Episodes(season)) => {
-            match rename_season(&season, args.episode_index, args, preview_list) {
-                Ok(_) => {
-                    if args.flags & DRY_RUN != 0 { return }
-                    info_bar.set_message_type(gtk::MessageType::Info);
-                    notification_label.set_text("Rename Success");
-                },
-                Err(why) => match_rename_error(info_bar, notification_label, why, args)
-            }
-        },
-        Ok(ScanDir::Seasons(seasons))  => {
-            for season in seasons {
-                match rename_season(&season, 1, args, preview_list) {
-                    Ok(_) => {
-                        if args.flags & DRY_RUN != 0 { return }
-                        info_bar.set_message_type(gtk::MessageType::Info);
-                        notification_label.set_text("Rename Success");
-                    },
-                    Err(why) => {
-                        match_rename_error(info_bar, notification_label, why, args);
-                        break
-                    }
-                }
-            }
-        },
+    let mut plan = RenamePlan::new(args.action);
+    plan.series_name = args.series_name.clone();
+    let scanned = match backend::scan_directory(&args.base_directory, args.season_index, &args.scan) {
+        Ok(scanned) => scanned,
         Err(why) => {
             info_bar.set_message_type(gtk::MessageType::Error);
             notification_label.set_text(why.description());
+            info_bar.show();
+            return
+        }
+    };
+
+    let (mut seasons, mixed) = match scanned {
+        ScanDir::Episodes(season) => (vec![(season, args.episode_index)], false),
+        ScanDir::Seasons(seasons) => (seasons.into_iter().map(|season| (season, 1)).collect(), false),
+        ScanDir::Mixed(seasons)   => (seasons.into_iter().map(|season| (season, 1)).collect(), true)
+    };
+
+    // Every season is named from the same episode guide, which is only requested once.
+    let mut matched = Vec::new();
+    let provider = provider::open(settings);
+    let planned = provider::lookup(&provider, &args.series_name)
+        .and_then(|series_id| provider.episodes(series_id, args.ordering).map(|guide| (series_id, guide)))
+        .map_err(RenameErr::SeriesLookupFailed)
+        .and_then(|(series_id, guide)| {
+            // Files which give no episode numbers may be numbered by their durations, rather than by their order.
+            if args.match_runtime {
+                for &mut (ref mut season, episode_start) in &mut seasons {
+                    if let Some(proposal) = runtime::propose(season, episode_start, &guide) {
+                        if proposal.is_confident() { proposal.apply(season); }
+                        matched.push((season.season_no, proposal));
+                    }
+                }
+            }
+
+            // The seasons of a mixed directory are numbered by the names of their files, so that a missing episode
+            // leaves a gap. A season in which some names give no number is only previewed, in order.
+            let mut numbered = Vec::new();
+            for &(ref season, episode_start) in &seasons {
+                let numbers = match season.numbers_by_name() {
+                    Some(numbers) if mixed => numbers,
+                    None if mixed && args.flags & DRY_RUN == 0 => return Err(RenameErr::Unnumbered(season.season_no)),
+                    _ => season.numbers_in_order(episode_start)
+                };
+                numbered.push((season, numbers));
+            }
+
+            // Episodes are numbered by their position, so every season is compared with the guide first. Nothing is
+            // renamed unless they match, but a preview shows the episodes that could be named, along with the report.
+            let reports = numbered.iter()
+                .map(|&(season, ref numbers)| preflight::check(season, numbers, &guide))
+                .filter(Report::has_problems)
+                .collect::<Vec<Report>>();
+            if !reports.is_empty() && args.flags & DRY_RUN == 0 {
+                return Err(RenameErr::Mismatched(reports))
+            }
+
+            for &(season, ref numbers) in &numbered {
+                plan_season(season, numbers, args, series_id, &guide, &mut plan)?;
+            }
+            if reports.is_empty() { Ok(()) } else { Err(RenameErr::Mismatched(reports)) }
+        });
+
+    // Decide what to do with files that already exist, and ensure that no two files are renamed to the same name.
+    let planned = planned
+        .and_then(|_| plan.resolve_conflicts(args.on_conflict, ask_conflict).map_err(RenameErr::RenameFailed))
+        .and_then(|_| plan.schedule().map_err(RenameErr::RenameFailed));
+
+    for operation in &plan.displaced {
+        update_preview(preview_list, &operation.target, Path::new("Trash"));
+    }
+
+    for operation in &plan.operations {
+        update_preview(preview_list, &operation.source, &operation.target);
+    }
+
+    // Samples, trailers and other videos which were left out of the numbering are shown along with the renames.
+    if args.scan.junk == JunkPolicy::Exclude {
+        for &(ref season, _) in &seasons {
+            for junk in &season.junk {
+                update_preview(preview_list, &junk.path, Path::new(&format!("Excluded ({})", junk.kind.as_str())));
+            }
         }
     }
+
+    // Rename every file as a single transaction, which is rolled back if any rename fails, and then journal the
+    // renames so that they may be reverted with the Undo button.
+    let renamed = planned.and_then(|_| {
+        if args.flags & DRY_RUN != 0 || plan.is_empty() { return Ok(false) }
+        let outcome = plan.execute().map_err(RenameErr::RenameFailed)?;
+        Batch::new(&plan.series_name).record(&plan, outcome).map_err(RenameErr::JournalFailed)?;
+        Ok(true)
+    });
+
+    match renamed {
+        // Episodes are numbered by their position, so a preview states the order that they were sorted in.
+        Ok(false) if args.flags & DRY_RUN != 0 && !plan.is_empty() => {
+            info_bar.set_message_type(gtk::MessageType::Info);
+            let mut message = format!("Episodes are numbered in {}", args.scan.sort.description());
+            for &(season_no, ref proposal) in &matched {
+                let percent = (proposal.confidence * 100.0).round();
+                if proposal.is_confident() {
+                    message.push_str(&format!("\nSeason {} is numbered by runtime, with {}% confidence", season_no,
+                        percent));
+                } else {
+                    message.push_str(&format!("\nSeason {} is numbered in order, as its runtimes are ambiguous ({}% \
+                        confidence)", season_no, percent));
+                }
+            }
+            notification_label.set_text(&message);
+        },
+        Ok(false) => return,
+        Ok(true) => {
+            info_bar.set_message_type(gtk::MessageType::Info);
+            notification_label.set_text("Rename Success");
+        },
+        Err(why) => match_rename_error(info_bar, notification_label, why, args)
+    }
     info_bar.show();
 }
 
@@ -201,43 +348,86 @@ fn rename_series(args: &Arguments, preview_list: &ListStore, info_bar: &gtk::Inf
 fn match_rename_error(info_bar: &gtk::InfoBar, notification_label: &gtk::Label, why: RenameErr, args: &Arguments) {
     info_bar.set_message_type(gtk::MessageType::Error);
     let message = match why {
-        RenameErr::RenameFailed(source, target) => format!("Could not rename {:?} to {:?}", source, target),
-        RenameErr::TargetExists(path)           => format!("{:?} already exists", path),
-        RenameErr::EpisodeDoesNotExist(episode, err) => format!("Episode {} could not be found on TheTVDB, err={}", episode, err),
-        RenameErr::SeriesLookupFailed           => format!("{} could not be found on TheTVDB", &args.series_name)
+        RenameErr::Mismatched(reports) => {
+            info_bar.set_message_type(gtk::MessageType::Warning);
+            reports.iter().map(Report::to_string).collect::<Vec<_>>().join("\n")
+        },
+        RenameErr::RenameFailed(why)            => why.to_string(),
+        RenameErr::EpisodeDoesNotExist(episode) => format!("Episode {} could not be found on TheTVDB", episode),
+        RenameErr::Unnumbered(season)           => format!("Not every file of season {} gives its episode number, so \
+            they cannot be numbered by name", season),
+        RenameErr::SeriesLookupFailed(why)      => format!("Unable to get the episodes of {} from TheTVDB: {}", &args.series_name, why),
+        RenameErr::JournalFailed(why)           => format!("Renamed all files, but could not record them for undo: {}", why)
     };
     notification_label.set_text(message.as_str());
 }
 
 enum RenameErr {
-    TargetExists(PathBuf),
-    RenameFailed(PathBuf, PathBuf),
-    EpisodeDoesNotExist(u16, String),
-    SeriesLookupFailed
+    RenameFailed(PlanError),
+    EpisodeDoesNotExist(u32),
+    /// A season of a mixed directory in which some file names give no episode number.
+    Unnumbered(u16),
+    SeriesLookupFailed(ProviderError),
+    Mismatched(Vec<Report>),
+    JournalFailed(JournalError)
 }
 
-/// Renames a given season and updates the preview for each episode renamed.
-/// If executed with `arguments.dry_run` set to true, the preview will be updated but the files will not be renamed.
-fn rename_season(season: &Season, episode_no: u16, arguments: &Arguments, preview_list: &ListStore)
-    -> Result<(), RenameErr>
+/// Asks the user what to do about an existing target.
+fn ask_conflict(operation: &Operation) -> ConflictPolicy {
+    let dialog = MessageDialog::new(
+        None::<&Window>,
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::None,
+        &format!("{:?} already exists.", operation.target)
+    );
+    dialog.add_button("Skip", gtk::ResponseType::Other(0));
+    dialog.add_button("Overwrite", gtk::ResponseType::Other(1));
+    dialog.add_button("Keep Both", gtk::ResponseType::Other(2));
+    dialog.add_button("Move to Trash", gtk::ResponseType::Other(3));
+    dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+
+    let policy = match dialog.run() {
+        gtk::ResponseType::Other(0) => ConflictPolicy::Skip,
+        gtk::ResponseType::Other(1) => ConflictPolicy::Overwrite,
+        gtk::ResponseType::Other(2) => ConflictPolicy::Suffix,
+        gtk::ResponseType::Other(3) => ConflictPolicy::Trash,
+        _                           => ConflictPolicy::Abort
+    };
+
+    // call to unsafe function
+    unsafe {
+        dialog.destroy();
+    }
+    policy
+}
+
+/// Plans the renaming of a given season.
+fn plan_season(season: &Season, numbers: &[u32], arguments: &Arguments, series_id: u32, guide: &[EpisodeInfo],
+    plan: &mut RenamePlan) -> Result<(), RenameErr>
 {
-    let mut episode_no = episode_no;
-
-    // TVDB
-    let api = tvdb::Tvdb::new("0629B785CE550C8D");
-    let series_id = api.search(Some(&arguments.series_name), Some("en")).map_err(|_| RenameErr::SeriesLookupFailed)?.data.unwrap()[0].id.unwrap();
-
-    for source in &season.episodes {
-        let target = backend::collect_target(source, season.season_no, episode_no, arguments, &api, series_id)
-            .map_err(|err| RenameErr::EpisodeDoesNotExist(episode_no, err.description))?;
-        if target.exists() { return Err(RenameErr::TargetExists(source.clone())); }
-        update_preview(preview_list, source, &target);
-        if arguments.flags & DRY_RUN == 0 {
-            fs::rename(&source, &target).map_err(|_| RenameErr::RenameFailed(source.clone(), target))?;
+    for (episode, &episode_no) in season.episodes.iter().zip(numbers) {
+        let source = &episode.path;
+        let target = match backend::collect_target(source, season.season_no, episode_no, arguments, guide) {
+            Ok(target) => target,
+            // Previews leave out the files which were reported as extra.
+            Err(TargetErr::EpisodeDoesNotExist) if arguments.flags & DRY_RUN != 0 => continue,
+            Err(_) => return Err(RenameErr::EpisodeDoesNotExist(episode_no))
+        };
+
+        // Companion files follow the video to its new stem, keeping their language and flag suffixes.
+        let sidecars = episode.sidecars.iter()
+            .filter_map(|sidecar| sidecar::target(source, &target, sidecar).map(|target| (sidecar.clone(), target)))
+            .collect::<Vec<_>>();
+
+        for (source, target) in Some((source.clone(), target)).into_iter().chain(sidecars) {
+            plan.push(Operation {
+                source: source, target: target, series_id: series_id,
+                season_no: season.season_no, episode_no: episode_no
+            });
         }
-        episode_no += 1;
     }
-    Ok(()) // Rename success
+    Ok(())
 }
 
 #[inline]
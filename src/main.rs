@@ -1,8 +1,16 @@
-extern crate tvdb;
+extern crate ureq;
+extern crate toml;
+extern crate ignore;
+extern crate regex;
 extern crate gtk;
 extern crate gdk;
 #[macro_use] extern crate quick_error;
 extern crate glib;
+extern crate libc;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate serde_json;
+#[macro_use] extern crate clap;
 
 mod backend;
 mod frontend {
@@ -11,12 +19,19 @@ mod frontend {
 }
 
 use std::env;
+use std::io::{self, Write};
+use std::process;
 
 fn main() {
-    let mut arguments = env::args();
-    if arguments.next().unwrap().ends_with("tv-renamer-gtk") {
-        frontend::gtk3::interface();
+    if env::args().next().map_or(false, |program| program.ends_with("tv-renamer-gtk")) {
+        match backend::config::load(None) {
+            Ok(settings) => frontend::gtk3::interface(settings),
+            Err(why) => {
+                let _ = writeln!(io::stderr(), "tv-renamer: {}", why);
+                process::exit(1);
+            }
+        }
     } else {
-        frontend::cli::interface(arguments);
+        frontend::cli::interface(env::args());
     }
 }